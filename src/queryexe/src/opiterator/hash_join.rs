@@ -3,9 +3,76 @@ use crate::Managers;
 
 use common::bytecode_expr::ByteCodeExpr;
 use common::{CrustyError, Field, TableSchema, Tuple};
+use std::collections::hash_map::DefaultHasher;
 use std::collections::HashMap;
+use std::fs::{self, File, OpenOptions};
+use std::hash::{Hash, Hasher};
+use std::io::{BufReader, Read, Write};
+use std::path::PathBuf;
+
+/// Left tuples are buffered in memory up to this many tuples before we give
+/// up on an in-memory build and fall back to grace (partitioned) hash join.
+const DEFAULT_MEMORY_BUDGET: usize = 100_000;
+/// Number of disk partitions the build and probe sides are split into once
+/// the build side spills.
+const DEFAULT_NUM_PARTITIONS: usize = 16;
+
+/// A single partition's spill file, holding length-prefixed, CBOR-encoded
+/// tuples appended during the partitioning pass and read back (in full,
+/// once) when that partition's turn to join comes up.
+struct PartitionFile {
+    path: PathBuf,
+}
+
+impl PartitionFile {
+    fn append(&self, tuple: &Tuple) -> Result<(), CrustyError> {
+        let bytes = serde_cbor::to_vec(tuple).map_err(|e| {
+            CrustyError::CrustyError(format!("Could not serialize spilled tuple: {}", e))
+        })?;
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .map_err(|e| {
+                CrustyError::CrustyError(format!("Could not open partition spill file: {}", e))
+            })?;
+        file.write_all(&(bytes.len() as u32).to_le_bytes())
+            .and_then(|_| file.write_all(&bytes))
+            .map_err(|e| {
+                CrustyError::CrustyError(format!("Could not write partition spill file: {}", e))
+            })
+    }
+
+    fn read_all(&self) -> Result<Vec<Tuple>, CrustyError> {
+        let file = match File::open(&self.path) {
+            Ok(file) => file,
+            Err(_) => return Ok(Vec::new()), // partition never got a write
+        };
+        let mut reader = BufReader::new(file);
+        let mut tuples = Vec::new();
+        let mut len_buf = [0u8; 4];
+        while reader.read_exact(&mut len_buf).is_ok() {
+            let mut bytes = vec![0u8; u32::from_le_bytes(len_buf) as usize];
+            reader.read_exact(&mut bytes).map_err(|e| {
+                CrustyError::CrustyError(format!("Could not read partition spill file: {}", e))
+            })?;
+            tuples.push(serde_cbor::from_slice(&bytes).map_err(|e| {
+                CrustyError::CrustyError(format!("Could not deserialize spilled tuple: {}", e))
+            })?);
+        }
+        Ok(tuples)
+    }
+}
 
 /// Hash equi-join implementation. (You can add any other fields that you think are neccessary)
+///
+/// Starts out buffering the left (build) side into `left_hashmap` the way a
+/// plain in-memory hash join would. If that buffer grows past
+/// `memory_budget`, `open` instead falls back to a grace (partitioned) hash
+/// join: both sides are hashed by their join expression into
+/// `num_partitions` on-disk spill files, and the probe phase then joins one
+/// partition pair at a time, loading only that left partition into
+/// `left_hashmap` at once.
 pub struct HashEqJoin {
     // Static objects (No need to reset on close)
     #[allow(dead_code)]
@@ -17,11 +84,26 @@ pub struct HashEqJoin {
     right_expr: ByteCodeExpr,
     left_child: Box<dyn OpIterator>,
     right_child: Box<dyn OpIterator>,
+    memory_budget: usize,
+    num_partitions: usize,
     // States (Need to reset on close)
     open: bool,
     current_tuple: Option<Tuple>,
     current_index: Option<usize>,
     left_hashmap: HashMap<Field, Vec<Tuple>>,
+    /// Set once the build side has spilled to disk; `false` means every
+    /// right tuple is still read straight from `right_child`, as before.
+    partitioned: bool,
+    /// Directory holding this join's spill files, if any; removed on close.
+    spill_dir: Option<PathBuf>,
+    left_partitions: Vec<PartitionFile>,
+    right_partitions: Vec<PartitionFile>,
+    /// Index of the partition currently loaded into `left_hashmap`.
+    cur_partition: usize,
+    /// The current partition's right tuples, read back in full from its
+    /// spill file; `right_idx` tracks how far we've streamed through it.
+    right_buffer: Vec<Tuple>,
+    right_idx: usize,
 }
 
 impl HashEqJoin {
@@ -33,6 +115,11 @@ impl HashEqJoin {
     /// * `right_expr` - ByteCodeExpr for the right field in join condition.
     /// * `left_child` - Left child of join operator.
     /// * `right_child` - Left child of join operator.
+    ///
+    /// Validates `left_expr` and `right_expr` against `schema` up front
+    /// (see [`ByteCodeExpr::validate`]) and confirms they evaluate to
+    /// comparable types, so a malformed predicate is rejected here instead
+    /// of panicking deep inside `eval` once the join is running.
     pub fn new(
         managers: &'static Managers,
         schema: TableSchema,
@@ -40,18 +127,107 @@ impl HashEqJoin {
         right_expr: ByteCodeExpr,
         left_child: Box<dyn OpIterator>,
         right_child: Box<dyn OpIterator>,
-    ) -> Self {
-        Self {
+    ) -> Result<Self, CrustyError> {
+        Self::new_with_config(
+            managers,
+            schema,
+            left_expr,
+            right_expr,
+            left_child,
+            right_child,
+            DEFAULT_MEMORY_BUDGET,
+            DEFAULT_NUM_PARTITIONS,
+        )
+    }
+
+    /// Like [`HashEqJoin::new`], but lets the caller tune when the build
+    /// side spills to disk (`memory_budget`, in tuples buffered) and how
+    /// many partitions it spills across (`num_partitions`) once it does.
+    #[allow(clippy::too_many_arguments)]
+    pub fn new_with_config(
+        managers: &'static Managers,
+        schema: TableSchema,
+        left_expr: ByteCodeExpr,
+        right_expr: ByteCodeExpr,
+        left_child: Box<dyn OpIterator>,
+        right_child: Box<dyn OpIterator>,
+        memory_budget: usize,
+        num_partitions: usize,
+    ) -> Result<Self, CrustyError> {
+        let left_type = left_expr.validate(&schema)?;
+        let right_type = right_expr.validate(&schema)?;
+        if left_type != right_type {
+            return Err(CrustyError::CrustyError(format!(
+                "HashEqJoin predicate type mismatch: left evaluates to {:?}, right evaluates to {:?}",
+                left_type, right_type
+            )));
+        }
+
+        Ok(Self {
             managers,
             schema,
             left_expr,
             right_expr,
             left_child,
             right_child,
+            memory_budget,
+            num_partitions,
             open: false,
             current_tuple: None,
             current_index: None,
             left_hashmap: HashMap::new(),
+            partitioned: false,
+            spill_dir: None,
+            left_partitions: Vec::new(),
+            right_partitions: Vec::new(),
+            cur_partition: 0,
+            right_buffer: Vec::new(),
+            right_idx: 0,
+        })
+    }
+
+    /// Which of `num_partitions` spill files a join-key value belongs in.
+    fn partition_of(&self, key: &Field) -> usize {
+        let mut hasher = DefaultHasher::new();
+        key.hash(&mut hasher);
+        (hasher.finish() as usize) % self.num_partitions
+    }
+
+    /// Load partition `idx`'s left tuples into `left_hashmap` and its right
+    /// tuples into `right_buffer`, ready to be probed by `next`.
+    fn load_partition(&mut self, idx: usize) -> Result<(), CrustyError> {
+        self.left_hashmap.clear();
+        self.right_idx = 0;
+        self.cur_partition = idx;
+        if idx >= self.num_partitions {
+            self.right_buffer = Vec::new();
+            return Ok(());
+        }
+        for tuple in self.left_partitions[idx].read_all()? {
+            let key = self.left_expr.eval(&tuple);
+            self.left_hashmap.entry(key).or_default().push(tuple);
+        }
+        self.right_buffer = self.right_partitions[idx].read_all()?;
+        Ok(())
+    }
+
+    /// The next right-side tuple to probe, however it's currently being
+    /// sourced: straight from `right_child` in the in-memory case, or by
+    /// streaming through spilled partitions in turn once we've grace-hashed.
+    fn next_right_tuple(&mut self) -> Result<Option<Tuple>, CrustyError> {
+        if !self.partitioned {
+            return self.right_child.next();
+        }
+        loop {
+            if self.right_idx < self.right_buffer.len() {
+                let tuple = self.right_buffer[self.right_idx].clone();
+                self.right_idx += 1;
+                return Ok(Some(tuple));
+            }
+            if self.cur_partition + 1 >= self.num_partitions {
+                return Ok(None);
+            }
+            self.load_partition(self.cur_partition + 1)?;
         }
     }
 }
@@ -63,26 +239,76 @@ impl OpIterator for HashEqJoin {
     }
 
     fn open(&mut self) -> Result<(), CrustyError> {
-        if !self.open {
-            self.left_child.open()?;
-            self.right_child.open()?;
-            self.open = true;
-
-            let hashmap = &mut self.left_hashmap;
-            while let Some(cur_tuple) = self.left_child.next()? {
-                let leftfield = self.left_expr.eval(&cur_tuple);
-                if hashmap.contains_key(&leftfield) {
-                    // add the value to the array under that hashkey
-                    let vec = hashmap.get_mut(&leftfield).unwrap();
-                    vec.push(cur_tuple);
-                } else {
-                    // initialize a new hash key value pair
-                    let vec = vec![cur_tuple];
-                    hashmap.insert(leftfield, vec);
-                }
+        if self.open {
+            return Ok(());
+        }
+        self.left_child.open()?;
+        self.right_child.open()?;
+        self.open = true;
+
+        // Buffer the build side in memory, same as a plain hash join,
+        // unless and until it grows past the budget.
+        let mut left_buffer = Vec::new();
+        let mut spilled = false;
+        while let Some(cur_tuple) = self.left_child.next()? {
+            left_buffer.push(cur_tuple);
+            if left_buffer.len() > self.memory_budget {
+                spilled = true;
+                break;
             }
         }
-        Ok(())
+
+        if !spilled {
+            for tuple in left_buffer {
+                let leftfield = self.left_expr.eval(&tuple);
+                self.left_hashmap.entry(leftfield).or_default().push(tuple);
+            }
+            return Ok(());
+        }
+
+        // The build side doesn't fit: hash-partition what's already
+        // buffered plus the rest of the left child across
+        // `num_partitions` spill files keyed by `left_expr`, then do the
+        // same for the (unbounded) right child keyed by `right_expr`. The
+        // probe phase then joins one partition pair at a time.
+        self.partitioned = true;
+        let dir = std::env::temp_dir().join(format!(
+            "crusty_hashjoin_{}_{}",
+            std::process::id(),
+            self as *const _ as usize
+        ));
+        fs::create_dir_all(&dir).map_err(|e| {
+            CrustyError::CrustyError(format!("Could not create partition spill dir: {}", e))
+        })?;
+        self.left_partitions = (0..self.num_partitions)
+            .map(|i| PartitionFile {
+                path: dir.join(format!("left_{}.part", i)),
+            })
+            .collect();
+        self.right_partitions = (0..self.num_partitions)
+            .map(|i| PartitionFile {
+                path: dir.join(format!("right_{}.part", i)),
+            })
+            .collect();
+        self.spill_dir = Some(dir);
+
+        for tuple in left_buffer {
+            let key = self.left_expr.eval(&tuple);
+            let p = self.partition_of(&key);
+            self.left_partitions[p].append(&tuple)?;
+        }
+        while let Some(tuple) = self.left_child.next()? {
+            let key = self.left_expr.eval(&tuple);
+            let p = self.partition_of(&key);
+            self.left_partitions[p].append(&tuple)?;
+        }
+        while let Some(tuple) = self.right_child.next()? {
+            let key = self.right_expr.eval(&tuple);
+            let p = self.partition_of(&key);
+            self.right_partitions[p].append(&tuple)?;
+        }
+
+        self.load_partition(0)
     }
 
     fn next(&mut self) -> Result<Option<Tuple>, CrustyError> {
@@ -104,9 +330,11 @@ impl OpIterator for HashEqJoin {
                 self.current_index = None;
             }
         }
-        // otherwise, get the next right tuple
-        while let Some(right_tuple) = &self.right_child.next()? {
-            let searchkey = self.right_expr.eval(right_tuple);
+        // otherwise, get the next right tuple, wherever it's being
+        // sourced from (the right child directly, or the spilled
+        // partitions once we've grace-hashed)
+        while let Some(right_tuple) = self.next_right_tuple()? {
+            let searchkey = self.right_expr.eval(&right_tuple);
             if self.left_hashmap.contains_key(&searchkey) {
                 let vec = self.left_hashmap.get(&searchkey).unwrap();
                 let left_tuple = &vec[0];
@@ -114,7 +342,7 @@ impl OpIterator for HashEqJoin {
                     self.current_tuple = Some(right_tuple.clone());
                     self.current_index = Some(1);
                 }
-                let t = left_tuple.merge(right_tuple);
+                let t = left_tuple.merge(&right_tuple);
                 return Ok(Some(t));
             } else {
                 continue;
@@ -129,6 +357,17 @@ impl OpIterator for HashEqJoin {
         self.right_child.close()?;
         self.open = false;
         self.left_hashmap.clear();
+        self.current_tuple = None;
+        self.current_index = None;
+        self.partitioned = false;
+        self.left_partitions.clear();
+        self.right_partitions.clear();
+        self.right_buffer.clear();
+        self.right_idx = 0;
+        self.cur_partition = 0;
+        if let Some(dir) = self.spill_dir.take() {
+            let _ = fs::remove_dir_all(dir);
+        }
         Ok(())
     }
 
@@ -137,6 +376,9 @@ impl OpIterator for HashEqJoin {
         self.right_child.rewind()?;
         self.current_tuple = None;
         self.current_index = None;
+        if self.partitioned {
+            self.load_partition(0)?;
+        }
 
         Ok(())
     }
@@ -182,20 +424,23 @@ mod test {
     fn get_iter(left_expr: ByteCodeExpr, right_expr: ByteCodeExpr) -> Box<dyn OpIterator> {
         let setup = TestTuples::new("");
         let managers = new_test_managers();
-        let mut iter = Box::new(HashEqJoin::new(
-            managers,
-            setup.schema.clone(),
-            left_expr,
-            right_expr,
-            Box::new(TupleIterator::new(
-                setup.tuples.clone(),
+        let mut iter = Box::new(
+            HashEqJoin::new(
+                managers,
                 setup.schema.clone(),
-            )),
-            Box::new(TupleIterator::new(
-                setup.tuples.clone(),
-                setup.schema.clone(),
-            )),
-        ));
+                left_expr,
+                right_expr,
+                Box::new(TupleIterator::new(
+                    setup.tuples.clone(),
+                    setup.schema.clone(),
+                )),
+                Box::new(TupleIterator::new(
+                    setup.tuples.clone(),
+                    setup.schema.clone(),
+                )),
+            )
+            .unwrap(),
+        );
         iter.configure(false);
         iter
     }
@@ -205,15 +450,58 @@ mod test {
         execute_iter(&mut *iter, true).unwrap()
     }
 
+    /// Like `get_iter`, but with a `memory_budget` of 1 tuple so `open`
+    /// always falls back to grace (partitioned) hash join.
+    fn get_spilling_iter(left_expr: ByteCodeExpr, right_expr: ByteCodeExpr) -> Box<dyn OpIterator> {
+        let setup = TestTuples::new("");
+        let managers = new_test_managers();
+        let mut iter = Box::new(
+            HashEqJoin::new_with_config(
+                managers,
+                setup.schema.clone(),
+                left_expr,
+                right_expr,
+                Box::new(TupleIterator::new(
+                    setup.tuples.clone(),
+                    setup.schema.clone(),
+                )),
+                Box::new(TupleIterator::new(
+                    setup.tuples.clone(),
+                    setup.schema.clone(),
+                )),
+                1,
+                4,
+            )
+            .unwrap(),
+        );
+        iter.configure(false);
+        iter
+    }
+
     mod hash_eq_join_test {
         use super::*;
 
         #[test]
-        #[should_panic]
         fn test_empty_predicate_join() {
+            let setup = TestTuples::new("");
+            let managers = new_test_managers();
             let left_expr = ByteCodeExpr::new();
             let right_expr = ByteCodeExpr::new();
-            let _ = run_hash_eq_join(left_expr, right_expr);
+            let res = HashEqJoin::new(
+                managers,
+                setup.schema.clone(),
+                left_expr,
+                right_expr,
+                Box::new(TupleIterator::new(
+                    setup.tuples.clone(),
+                    setup.schema.clone(),
+                )),
+                Box::new(TupleIterator::new(
+                    setup.tuples.clone(),
+                    setup.schema.clone(),
+                )),
+            );
+            assert!(res.is_err());
         }
 
         #[test]
@@ -275,6 +563,9 @@ mod test {
                     Field::String("A".to_string()),
                 ])
             );
+            // Right tuple 4 (`4 2 4 G`) probes the same left bucket
+            // (key 4) as right tuple 3, confirming a bucket can be probed
+            // by more than one right tuple in a row.
             assert_eq!(
                 t[3],
                 Tuple::new(vec![
@@ -289,6 +580,59 @@ mod test {
                 ])
             );
         }
+
+        #[test]
+        fn test_join_with_forced_spill() {
+            // Same tables and predicate as `test_join`, but with a
+            // `memory_budget` of 1 tuple so the build side always spills
+            // into a grace (partitioned) hash join. Partitioning processes
+            // right tuples out of their original order, so we check the
+            // result set rather than row-by-row positions.
+            let (left_expr, right_expr) = get_join_predicate();
+            let mut iter = get_spilling_iter(left_expr, right_expr);
+            let t = execute_iter(&mut *iter, true).unwrap();
+            assert_eq!(t.len(), 4);
+            assert!(t.contains(&Tuple::new(vec![
+                Field::Int(2),
+                Field::Int(1),
+                Field::Int(3),
+                Field::String("G".to_string()),
+                Field::Int(1),
+                Field::Int(1),
+                Field::Int(3),
+                Field::String("E".to_string()),
+            ])));
+            assert!(t.contains(&Tuple::new(vec![
+                Field::Int(2),
+                Field::Int(1),
+                Field::Int(3),
+                Field::String("G".to_string()),
+                Field::Int(2),
+                Field::Int(1),
+                Field::Int(3),
+                Field::String("G".to_string()),
+            ])));
+            assert!(t.contains(&Tuple::new(vec![
+                Field::Int(3),
+                Field::Int(1),
+                Field::Int(4),
+                Field::String("A".to_string()),
+                Field::Int(3),
+                Field::Int(1),
+                Field::Int(4),
+                Field::String("A".to_string()),
+            ])));
+            assert!(t.contains(&Tuple::new(vec![
+                Field::Int(3),
+                Field::Int(1),
+                Field::Int(4),
+                Field::String("A".to_string()),
+                Field::Int(4),
+                Field::Int(2),
+                Field::Int(4),
+                Field::String("G".to_string()),
+            ])));
+        }
     }
 
     mod opiterator_test {
@@ -1,58 +1,367 @@
 use super::OpIterator;
 
 use common::bytecode_expr::ByteCodeExpr;
-use common::datatypes::compare_fields;
-use common::{BooleanOp, CrustyError, TableSchema, Tuple};
+use common::datatypes::{compare_fields, DataType};
+use common::{BooleanOp, CrustyError, Field, TableSchema, Tuple};
 
-/// Nested loop join implementation. (You can add any other fields that you think are neccessary)
+/// The predicate a [`NestedLoopJoin`] checks against each left/right tuple
+/// pair it considers.
+enum JoinCondition {
+    /// `op(left_expr(left), right_expr(right))`, each evaluated against its
+    /// own side's tuple. The convenience form built by `NestedLoopJoin::new`
+    /// for a single equi/theta-join column pair.
+    Compare {
+        op: BooleanOp,
+        left_expr: ByteCodeExpr,
+        right_expr: ByteCodeExpr,
+    },
+    /// An arbitrary boolean expression evaluated against the merged
+    /// `left.merge(&right)` candidate tuple, so a single join node can
+    /// express conjunctions/disjunctions over several columns from either
+    /// side (e.g. `l.a = r.x AND l.b < r.y`). `PushField` indices are
+    /// resolved against the combined schema: left columns first, then
+    /// right.
+    Predicate(ByteCodeExpr),
+}
+
+impl JoinCondition {
+    /// Whether this condition still makes sense after `left_child`'s and
+    /// `right_child`'s roles are swapped. Only `Compare` tracks which
+    /// physical side each half of the comparison reads from; a `Predicate`'s
+    /// `PushField` indices are baked in against the original combined
+    /// schema, so swapping the children out from under it would silently
+    /// compare the wrong columns.
+    fn can_swap(&self) -> bool {
+        matches!(self, JoinCondition::Compare { .. })
+    }
+
+    /// Swaps `left_expr`/`right_expr` and flips `op` to match
+    /// `left_child`/`right_child` having just been swapped. No-op for
+    /// `Predicate`; callers must check `can_swap()` first.
+    fn swap(&mut self) {
+        if let JoinCondition::Compare {
+            op,
+            left_expr,
+            right_expr,
+        } = self
+        {
+            std::mem::swap(left_expr, right_expr);
+            *op = NestedLoopJoin::flip_op(*op);
+        }
+    }
+
+    fn matches(&self, left: &Tuple, right: &Tuple) -> bool {
+        match self {
+            JoinCondition::Compare {
+                op,
+                left_expr,
+                right_expr,
+            } => compare_fields(*op, &left_expr.eval(left), &right_expr.eval(right)),
+            JoinCondition::Predicate(expr) => matches!(expr.eval(&left.merge(right)), Field::Bool(true)),
+        }
+    }
+}
+
+/// Which rows a [`NestedLoopJoin`] must emit even when they have no match
+/// on the other side.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JoinType {
+    /// Only emit matched pairs.
+    Inner,
+    /// Also emit every unmatched left tuple, padded with `Field::Null` on
+    /// the right.
+    Left,
+    /// Also emit every unmatched right tuple, padded with `Field::Null` on
+    /// the left.
+    Right,
+    /// Both `Left` and `Right` behavior combined.
+    FullOuter,
+}
+
+/// Left tuples are buffered into a block of up to this many tuples before
+/// the right child is scanned, unless overridden via
+/// [`NestedLoopJoin::new_with_block_size`].
+const DEFAULT_BLOCK_SIZE: usize = 1_000;
+
+/// Block nested loop join implementation. Buffers up to `block_size` left
+/// tuples at a time and scans the right child once per block instead of
+/// once per left tuple, trading memory for far fewer right-child rewinds
+/// (ceil(n/block_size) scans instead of n).
 pub struct NestedLoopJoin {
     // Parameters (No need to reset on close)
     schema: TableSchema,
-    op: BooleanOp,
-    left_expr: ByteCodeExpr,
-    right_expr: ByteCodeExpr,
+    condition: JoinCondition,
+    join_type: JoinType,
     left_child: Box<dyn OpIterator>,
     right_child: Box<dyn OpIterator>,
+    block_size: usize,
+    /// Number of fields in a right-child tuple, used to build the
+    /// null-padded right side of an unmatched left tuple.
+    right_width: usize,
+    /// Number of fields in a left-child tuple, used to build the
+    /// null-padded left side of an unmatched right tuple.
+    left_width: usize,
+    /// Set by `configure()` when `left_child`/`right_child` were swapped
+    /// because the right side reported the larger `estimated_size()`, so
+    /// the rescanned (inner) side of the physical join is the smaller
+    /// relation. `next()` un-swaps the column order of every tuple it
+    /// produces so output stays in the caller's original (left, right)
+    /// order regardless.
+    swapped: bool,
 
-    // TODO: Add any other fields that you need to
-    // maintain operator state here
+    // States (Need to reset on close)
     open: bool,
-    current_tuple: Option<Tuple>,
+    /// The current block of buffered left tuples.
+    block: Vec<Tuple>,
+    /// Whether each tuple in `block` has matched at least one right tuple
+    /// so far this block; only meaningful for `Left`/`FullOuter` joins.
+    block_matched: Vec<bool>,
+    /// Index into `block` of the next left tuple to compare against
+    /// `right_tuple`.
+    block_idx: usize,
+    /// Index into `block` for the post-scan pass emitting this block's
+    /// unmatched left tuples.
+    flush_idx: usize,
+    /// Lookahead tuple from `right_child` for the block currently being
+    /// probed.
+    right_tuple: Option<Tuple>,
+    /// Position of `right_tuple` within the current pass over `right_child`;
+    /// used to index `right_matched`, which is shared across every block's
+    /// pass since a right tuple can match a left tuple in any block.
+    right_pos: usize,
+    /// Whether each right tuple (by position in a scan) has ever matched a
+    /// left tuple; only populated for `Right`/`FullOuter` joins.
+    right_matched: Vec<bool>,
+    /// Set once a block fill finds the left child exhausted; drives the
+    /// final pass emitting unmatched right tuples for `Right`/`FullOuter`.
+    left_done: bool,
 }
 
 impl NestedLoopJoin {
-    /// NestedLoopJoin constructor. Creates a new node for a nested-loop join.
+    /// NestedLoopJoin constructor. Creates a new node for a nested-loop join
+    /// with the default block size (see [`NestedLoopJoin::new_with_block_size`]).
+    /// A convenience over a single `op(left_expr, right_expr)` comparison;
+    /// for an arbitrary multi-column predicate see
+    /// [`NestedLoopJoin::new_with_predicate`].
     ///
     /// # Arguments
     ///
     /// * `op` - Operation in join condition.
+    /// * `join_type` - Inner/Left/Right/FullOuter behavior for unmatched rows.
     /// * `left_expr` - ByteCodeExpr for the left field in join condition.
     /// * `right_expr` - ByteCodeExpr for the right field in join condition.
     /// * `left_child` - Left child of join operator.
     /// * `right_child` - Left child of join operator.
+    ///
+    /// Validates `left_expr` and `right_expr` against `schema` up front
+    /// (see [`ByteCodeExpr::validate`]) and confirms they evaluate to
+    /// comparable types, so a malformed predicate is rejected here instead
+    /// of panicking deep inside `eval` once the join is running.
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         op: BooleanOp,
+        join_type: JoinType,
         left_expr: ByteCodeExpr,
         right_expr: ByteCodeExpr,
         left_child: Box<dyn OpIterator>,
         right_child: Box<dyn OpIterator>,
         schema: TableSchema,
-    ) -> Self {
-        Self {
+    ) -> Result<Self, CrustyError> {
+        Self::new_with_block_size(
             op,
+            join_type,
+            left_expr,
+            right_expr,
+            left_child,
+            right_child,
             schema,
+            DEFAULT_BLOCK_SIZE,
+        )
+    }
+
+    /// Like [`NestedLoopJoin::new`], but lets the caller tune how many left
+    /// tuples are buffered per block before scanning the right child.
+    #[allow(clippy::too_many_arguments)]
+    pub fn new_with_block_size(
+        op: BooleanOp,
+        join_type: JoinType,
+        left_expr: ByteCodeExpr,
+        right_expr: ByteCodeExpr,
+        left_child: Box<dyn OpIterator>,
+        right_child: Box<dyn OpIterator>,
+        schema: TableSchema,
+        block_size: usize,
+    ) -> Result<Self, CrustyError> {
+        let left_type = left_expr.validate(&schema)?;
+        let right_type = right_expr.validate(&schema)?;
+        if left_type != right_type {
+            return Err(CrustyError::CrustyError(format!(
+                "NestedLoopJoin predicate type mismatch: left evaluates to {:?}, right evaluates to {:?}",
+                left_type, right_type
+            )));
+        }
+
+        let condition = JoinCondition::Compare {
+            op,
             left_expr,
             right_expr,
+        };
+        Self::new_with_condition(condition, join_type, left_child, right_child, schema, block_size)
+    }
+
+    /// Creates a new node for a theta-join driven by an arbitrary boolean
+    /// `predicate`, evaluated against the merged `left.merge(&right)`
+    /// candidate tuple rather than a single `op(left_expr, right_expr)`
+    /// comparison. `PushField` indices in `predicate` must be resolved
+    /// against the combined schema (left columns first, then right), which
+    /// is validated up front by confirming `predicate` evaluates to
+    /// `DataType::Bool` against `schema`.
+    pub fn new_with_predicate(
+        predicate: ByteCodeExpr,
+        join_type: JoinType,
+        left_child: Box<dyn OpIterator>,
+        right_child: Box<dyn OpIterator>,
+        schema: TableSchema,
+    ) -> Result<Self, CrustyError> {
+        let predicate_type = predicate.validate(&schema)?;
+        if predicate_type != DataType::Bool {
+            return Err(CrustyError::CrustyError(format!(
+                "NestedLoopJoin predicate must evaluate to a boolean, got {:?}",
+                predicate_type
+            )));
+        }
+
+        Self::new_with_condition(
+            JoinCondition::Predicate(predicate),
+            join_type,
+            left_child,
+            right_child,
+            schema,
+            DEFAULT_BLOCK_SIZE,
+        )
+    }
+
+    fn new_with_condition(
+        condition: JoinCondition,
+        join_type: JoinType,
+        left_child: Box<dyn OpIterator>,
+        right_child: Box<dyn OpIterator>,
+        schema: TableSchema,
+        block_size: usize,
+    ) -> Result<Self, CrustyError> {
+        let left_width = left_child.get_schema().attributes().count();
+        let right_width = right_child.get_schema().attributes().count();
+
+        Ok(Self {
+            condition,
+            join_type,
+            schema,
             left_child,
             right_child,
+            block_size: block_size.max(1),
+            left_width,
+            right_width,
+            swapped: false,
             open: false,
-            current_tuple: None,
+            block: Vec::new(),
+            block_matched: Vec::new(),
+            block_idx: 0,
+            flush_idx: 0,
+            right_tuple: None,
+            right_pos: 0,
+            right_matched: Vec::new(),
+            left_done: false,
+        })
+    }
+
+    fn null_tuple(width: usize) -> Tuple {
+        Tuple::new(vec![Field::Null; width])
+    }
+
+    /// The comparison that keeps `op`'s meaning when its two operands are
+    /// swapped, e.g. `a < b` becomes `b > a`.
+    fn flip_op(op: BooleanOp) -> BooleanOp {
+        match op {
+            BooleanOp::Lt => BooleanOp::Gt,
+            BooleanOp::Gt => BooleanOp::Lt,
+            BooleanOp::Leq => BooleanOp::Geq,
+            BooleanOp::Geq => BooleanOp::Leq,
+            BooleanOp::Eq => BooleanOp::Eq,
+            BooleanOp::Neq => BooleanOp::Neq,
+        }
+    }
+
+    /// The `JoinType` that preserves the same unmatched-row guarantees once
+    /// `left_child`/`right_child` have been swapped, e.g. `Left` (preserve
+    /// unmatched left tuples) becomes `Right` once those tuples are held in
+    /// `right_child`.
+    fn flip_join_type(join_type: JoinType) -> JoinType {
+        match join_type {
+            JoinType::Left => JoinType::Right,
+            JoinType::Right => JoinType::Left,
+            JoinType::Inner | JoinType::FullOuter => join_type,
+        }
+    }
+
+    /// Merges the block-side and probe-side tuple of a match (or a
+    /// null-padded row) back into the caller's original `(left, right)`
+    /// column order, undoing any swap `configure()` made for cost reasons.
+    fn emit(&self, block_side: &Tuple, probe_side: &Tuple) -> Tuple {
+        if self.swapped {
+            probe_side.merge(block_side)
+        } else {
+            block_side.merge(probe_side)
+        }
+    }
+
+    /// Refills `block` with up to `block_size` left tuples and repositions
+    /// `right_child` at the start of a fresh pass over it. If the left
+    /// child is exhausted, `block` is left empty and `left_done` is set;
+    /// the right child is still rewound once more so a final pass over it
+    /// (for `Right`/`FullOuter` joins) starts from the beginning.
+    fn fill_block(&mut self) -> Result<(), CrustyError> {
+        self.block.clear();
+        for _ in 0..self.block_size {
+            match self.left_child.next()? {
+                Some(t) => self.block.push(t),
+                None => break,
+            }
         }
+        self.block_matched = vec![false; self.block.len()];
+        self.block_idx = 0;
+        self.flush_idx = 0;
+        self.right_pos = 0;
+
+        if self.block.is_empty() {
+            self.left_done = true;
+            self.right_tuple = None;
+            if matches!(self.join_type, JoinType::Right | JoinType::FullOuter) {
+                self.right_child.rewind()?;
+            }
+        } else {
+            self.right_child.rewind()?;
+            self.right_tuple = self.right_child.next()?;
+        }
+        Ok(())
     }
 }
 
 impl OpIterator for NestedLoopJoin {
     fn configure(&mut self, will_rewind: bool) {
+        // The block-buffered (left) side is scanned once; the probe (right)
+        // side is rescanned once per block, so it should hold the smaller
+        // relation whenever both children can estimate their size.
+        if let (Some(left_size), Some(right_size)) =
+            (self.left_child.estimated_size(), self.right_child.estimated_size())
+        {
+            if right_size > left_size && self.condition.can_swap() {
+                std::mem::swap(&mut self.left_child, &mut self.right_child);
+                std::mem::swap(&mut self.left_width, &mut self.right_width);
+                self.condition.swap();
+                self.join_type = Self::flip_join_type(self.join_type);
+                self.swapped = !self.swapped;
+            }
+        }
         self.left_child.configure(will_rewind);
         self.right_child.configure(true); // right child will always be rewound by NLJ
     }
@@ -61,45 +370,101 @@ impl OpIterator for NestedLoopJoin {
         if !self.open {
             self.left_child.open()?;
             self.right_child.open()?;
-            self.current_tuple = self.left_child.next()?;
+            self.fill_block()?;
             self.open = true;
         }
         Ok(())
     }
 
-    /// Calculates the next tuple for a nested loop join.
+    /// Calculates the next tuple for a block nested loop join.
     fn next(&mut self) -> Result<Option<Tuple>, CrustyError> {
         if !self.open {
             panic!("Iterator is not open");
         }
-        while let Some(left_tuple) = &self.current_tuple {
-            let leftfield = self.left_expr.eval(left_tuple);
-            if let Some(right_tuple) = self.right_child.next()? {
-                let rightfield = self.right_expr.eval(&right_tuple);
-                if compare_fields(self.op, &leftfield, &rightfield) {
-                    let t = left_tuple.merge(&right_tuple);
-                    return Ok(Some(t));
-                } else {
+        loop {
+            if !self.left_done {
+                if let Some(right_tuple) = self.right_tuple.clone() {
+                    while self.block_idx < self.block.len() {
+                        let idx = self.block_idx;
+                        self.block_idx += 1;
+                        if self.condition.matches(&self.block[idx], &right_tuple) {
+                            self.block_matched[idx] = true;
+                            if matches!(self.join_type, JoinType::Right | JoinType::FullOuter) {
+                                if self.right_matched.len() <= self.right_pos {
+                                    self.right_matched.push(false);
+                                }
+                                self.right_matched[self.right_pos] = true;
+                            }
+                            return Ok(Some(self.emit(&self.block[idx].clone(), &right_tuple)));
+                        }
+                    }
+
+                    // Whole block checked against this right tuple; advance it.
+                    if matches!(self.join_type, JoinType::Right | JoinType::FullOuter)
+                        && self.right_matched.len() <= self.right_pos
+                    {
+                        self.right_matched.push(false);
+                    }
+                    self.right_pos += 1;
+                    self.block_idx = 0;
+                    self.right_tuple = self.right_child.next()?;
                     continue;
                 }
+
+                // Right side exhausted for this block: flush its unmatched
+                // left tuples, padded with nulls, before moving to the next.
+                if matches!(self.join_type, JoinType::Left | JoinType::FullOuter) {
+                    while self.flush_idx < self.block.len() {
+                        let idx = self.flush_idx;
+                        self.flush_idx += 1;
+                        if !self.block_matched[idx] {
+                            let t = self.emit(&self.block[idx].clone(), &Self::null_tuple(self.right_width));
+                            return Ok(Some(t));
+                        }
+                    }
+                }
+
+                self.fill_block()?;
+                continue;
             }
-            self.right_child.rewind()?;
-            self.current_tuple = self.left_child.next()?;
+
+            // Left side exhausted: for Right/FullOuter joins, make one final
+            // pass over the right child emitting every tuple that never
+            // matched any block.
+            if matches!(self.join_type, JoinType::Right | JoinType::FullOuter) {
+                while let Some(right_tuple) = self.right_child.next()? {
+                    let pos = self.right_pos;
+                    self.right_pos += 1;
+                    let matched = self.right_matched.get(pos).copied().unwrap_or(false);
+                    if !matched {
+                        return Ok(Some(self.emit(&Self::null_tuple(self.left_width), &right_tuple)));
+                    }
+                }
+            }
+            return Ok(None);
         }
-        Ok(None)
     }
 
     fn close(&mut self) -> Result<(), CrustyError> {
         self.left_child.close()?;
         self.right_child.close()?;
         self.open = false;
+        self.block.clear();
+        self.block_matched.clear();
+        self.block_idx = 0;
+        self.flush_idx = 0;
+        self.right_tuple = None;
+        self.right_pos = 0;
+        self.right_matched.clear();
+        self.left_done = false;
         Ok(())
     }
 
     fn rewind(&mut self) -> Result<(), CrustyError> {
         self.left_child.rewind()?;
-        self.right_child.rewind()?;
-        self.current_tuple = self.left_child.next()?;
+        self.right_matched.clear();
+        self.left_done = false;
+        self.fill_block()?;
         Ok(())
     }
 
@@ -146,22 +511,35 @@ mod test {
         op: BooleanOp,
         left_expr: ByteCodeExpr,
         right_expr: ByteCodeExpr,
+    ) -> Box<dyn OpIterator> {
+        get_iter_with_join_type(op, JoinType::Inner, left_expr, right_expr)
+    }
+
+    fn get_iter_with_join_type(
+        op: BooleanOp,
+        join_type: JoinType,
+        left_expr: ByteCodeExpr,
+        right_expr: ByteCodeExpr,
     ) -> Box<dyn OpIterator> {
         let setup = TestTuples::new("");
-        let mut iter = Box::new(NestedLoopJoin::new(
-            op,
-            left_expr,
-            right_expr,
-            Box::new(TupleIterator::new(
-                setup.tuples.clone(),
-                setup.schema.clone(),
-            )),
-            Box::new(TupleIterator::new(
-                setup.tuples.clone(),
+        let mut iter = Box::new(
+            NestedLoopJoin::new(
+                op,
+                join_type,
+                left_expr,
+                right_expr,
+                Box::new(TupleIterator::new(
+                    setup.tuples.clone(),
+                    setup.schema.clone(),
+                )),
+                Box::new(TupleIterator::new(
+                    setup.tuples.clone(),
+                    setup.schema.clone(),
+                )),
                 setup.schema.clone(),
-            )),
-            setup.schema.clone(),
-        ));
+            )
+            .unwrap(),
+        );
         iter.configure(false);
         iter
     }
@@ -179,11 +557,26 @@ mod test {
         use super::*;
 
         #[test]
-        #[should_panic]
         fn test_empty_predicate_join() {
+            let setup = TestTuples::new("");
             let left_expr = ByteCodeExpr::new();
             let right_expr = ByteCodeExpr::new();
-            let _ = run_nested_loop_join(BooleanOp::Eq, left_expr, right_expr);
+            let res = NestedLoopJoin::new(
+                BooleanOp::Eq,
+                JoinType::Inner,
+                left_expr,
+                right_expr,
+                Box::new(TupleIterator::new(
+                    setup.tuples.clone(),
+                    setup.schema.clone(),
+                )),
+                Box::new(TupleIterator::new(
+                    setup.tuples.clone(),
+                    setup.schema.clone(),
+                )),
+                setup.schema.clone(),
+            );
+            assert!(res.is_err());
         }
 
         #[test]
@@ -259,6 +652,328 @@ mod test {
                 ])
             );
         }
+
+        #[test]
+        fn test_left_outer_join_pads_unmatched_left_tuples() {
+            // left(col(0)) == right(col(0)), with a left tuple (key 99) that
+            // has no match on the right; it must still appear, padded with
+            // nulls on the right side.
+            let schema = TestTuples::new("").schema.clone();
+            let mut key_expr = ByteCodeExpr::new();
+            key_expr.add_code(ByteCodes::PushField as usize);
+            key_expr.add_code(0);
+
+            let left_tuples = vec![
+                Tuple::new(vec![
+                    Field::Int(1),
+                    Field::Int(1),
+                    Field::Int(1),
+                    Field::String("l0".to_string()),
+                ]),
+                Tuple::new(vec![
+                    Field::Int(99),
+                    Field::Int(1),
+                    Field::Int(1),
+                    Field::String("l1".to_string()),
+                ]),
+            ];
+            let right_tuples = vec![Tuple::new(vec![
+                Field::Int(1),
+                Field::Int(1),
+                Field::Int(1),
+                Field::String("r0".to_string()),
+            ])];
+
+            let mut iter = Box::new(
+                NestedLoopJoin::new(
+                    BooleanOp::Eq,
+                    JoinType::Left,
+                    key_expr.clone(),
+                    key_expr,
+                    Box::new(TupleIterator::new(left_tuples, schema.clone())),
+                    Box::new(TupleIterator::new(right_tuples, schema.clone())),
+                    schema,
+                )
+                .unwrap(),
+            );
+            iter.configure(false);
+            let t = execute_iter(&mut *iter, true).unwrap();
+            assert_eq!(t.len(), 2);
+            assert_eq!(
+                t[1],
+                Tuple::new(vec![
+                    Field::Int(99),
+                    Field::Int(1),
+                    Field::Int(1),
+                    Field::String("l1".to_string()),
+                    Field::Null,
+                    Field::Null,
+                    Field::Null,
+                    Field::Null,
+                ])
+            );
+        }
+
+        #[test]
+        fn test_full_outer_join_pads_unmatched_rows_on_both_sides() {
+            // Key 1 matches on both sides, key 2 only exists on the left,
+            // and key 3 only exists on the right.
+            let schema = TestTuples::new("").schema.clone();
+            let mut key_expr = ByteCodeExpr::new();
+            key_expr.add_code(ByteCodes::PushField as usize);
+            key_expr.add_code(0);
+
+            let left_tuples = vec![
+                Tuple::new(vec![
+                    Field::Int(1),
+                    Field::Int(1),
+                    Field::Int(1),
+                    Field::String("l0".to_string()),
+                ]),
+                Tuple::new(vec![
+                    Field::Int(2),
+                    Field::Int(1),
+                    Field::Int(1),
+                    Field::String("l1".to_string()),
+                ]),
+            ];
+            let right_tuples = vec![
+                Tuple::new(vec![
+                    Field::Int(1),
+                    Field::Int(1),
+                    Field::Int(1),
+                    Field::String("r0".to_string()),
+                ]),
+                Tuple::new(vec![
+                    Field::Int(3),
+                    Field::Int(1),
+                    Field::Int(1),
+                    Field::String("r1".to_string()),
+                ]),
+            ];
+
+            let mut iter = Box::new(
+                NestedLoopJoin::new(
+                    BooleanOp::Eq,
+                    JoinType::FullOuter,
+                    key_expr.clone(),
+                    key_expr,
+                    Box::new(TupleIterator::new(left_tuples, schema.clone())),
+                    Box::new(TupleIterator::new(right_tuples, schema.clone())),
+                    schema,
+                )
+                .unwrap(),
+            );
+            iter.configure(false);
+            let t = execute_iter(&mut *iter, true).unwrap();
+            // 1 match + 1 unmatched left + 1 unmatched right.
+            assert_eq!(t.len(), 3);
+            assert_eq!(
+                t[2],
+                Tuple::new(vec![
+                    Field::Null,
+                    Field::Null,
+                    Field::Null,
+                    Field::Null,
+                    Field::Int(3),
+                    Field::Int(1),
+                    Field::Int(1),
+                    Field::String("r1".to_string()),
+                ])
+            );
+        }
+
+        #[test]
+        fn test_block_size_smaller_than_left_side_still_matches_across_blocks() {
+            // Six left tuples with a block size of 2 forces three blocks, each
+            // rescanning the whole right side; results should be identical to
+            // a single block holding every left tuple.
+            let (left_expr, right_expr) = get_join_predicate();
+            let setup = TestTuples::new("");
+            let mut iter = Box::new(
+                NestedLoopJoin::new_with_block_size(
+                    BooleanOp::Eq,
+                    JoinType::Inner,
+                    left_expr,
+                    right_expr,
+                    Box::new(TupleIterator::new(
+                        setup.tuples.clone(),
+                        setup.schema.clone(),
+                    )),
+                    Box::new(TupleIterator::new(
+                        setup.tuples.clone(),
+                        setup.schema.clone(),
+                    )),
+                    setup.schema.clone(),
+                    2,
+                )
+                .unwrap(),
+            );
+            iter.configure(false);
+            let t = execute_iter(&mut *iter, true).unwrap();
+            assert_eq!(t.len(), 4);
+        }
+
+        #[test]
+        fn test_predicate_join_over_multiple_columns() {
+            // l.0 == r.0 AND l.1 < r.1, which a single op(left_expr,
+            // right_expr) comparison can't express.
+            let schema = TestTuples::new("").schema.clone();
+            let mut predicate = ByteCodeExpr::new();
+            predicate.add_code(ByteCodes::PushField as usize);
+            predicate.add_code(0);
+            predicate.add_code(ByteCodes::PushField as usize);
+            predicate.add_code(4);
+            predicate.add_code(ByteCodes::Eq as usize);
+            predicate.add_code(ByteCodes::PushField as usize);
+            predicate.add_code(1);
+            predicate.add_code(ByteCodes::PushField as usize);
+            predicate.add_code(5);
+            predicate.add_code(ByteCodes::Lt as usize);
+            predicate.add_code(ByteCodes::And as usize);
+
+            let left_tuples = vec![
+                Tuple::new(vec![
+                    Field::Int(1),
+                    Field::Int(1),
+                    Field::Int(1),
+                    Field::String("l0".to_string()),
+                ]),
+                Tuple::new(vec![
+                    Field::Int(1),
+                    Field::Int(5),
+                    Field::Int(1),
+                    Field::String("l1".to_string()),
+                ]),
+            ];
+            let right_tuples = vec![Tuple::new(vec![
+                Field::Int(1),
+                Field::Int(2),
+                Field::Int(1),
+                Field::String("r0".to_string()),
+            ])];
+
+            let mut iter = Box::new(
+                NestedLoopJoin::new_with_predicate(
+                    predicate,
+                    JoinType::Inner,
+                    Box::new(TupleIterator::new(left_tuples, schema.clone())),
+                    Box::new(TupleIterator::new(right_tuples, schema.clone())),
+                    schema,
+                )
+                .unwrap(),
+            );
+            iter.configure(false);
+            let t = execute_iter(&mut *iter, true).unwrap();
+            // Only "l0" has a matching key (1 == 1) and a smaller second
+            // column (1 < 2); "l1" matches the key but fails `l.1 < r.1`.
+            assert_eq!(
+                t,
+                vec![Tuple::new(vec![
+                    Field::Int(1),
+                    Field::Int(1),
+                    Field::Int(1),
+                    Field::String("l0".to_string()),
+                    Field::Int(1),
+                    Field::Int(2),
+                    Field::Int(1),
+                    Field::String("r0".to_string()),
+                ])]
+            );
+        }
+    }
+
+    /// Wraps a `TupleIterator` with a fixed `estimated_size()`, letting tests
+    /// exercise `configure()`'s cardinality-based child swap without needing
+    /// a real size-estimating operator upstream.
+    struct SizedIterator {
+        inner: TupleIterator,
+        size: usize,
+    }
+
+    impl SizedIterator {
+        fn new(tuples: Vec<Tuple>, schema: TableSchema, size: usize) -> Self {
+            SizedIterator {
+                inner: TupleIterator::new(tuples, schema),
+                size,
+            }
+        }
+    }
+
+    impl OpIterator for SizedIterator {
+        fn configure(&mut self, will_rewind: bool) {
+            self.inner.configure(will_rewind);
+        }
+
+        fn open(&mut self) -> Result<(), CrustyError> {
+            self.inner.open()
+        }
+
+        fn next(&mut self) -> Result<Option<Tuple>, CrustyError> {
+            self.inner.next()
+        }
+
+        fn close(&mut self) -> Result<(), CrustyError> {
+            self.inner.close()
+        }
+
+        fn rewind(&mut self) -> Result<(), CrustyError> {
+            self.inner.rewind()
+        }
+
+        fn get_schema(&self) -> &TableSchema {
+            self.inner.get_schema()
+        }
+
+        fn estimated_size(&self) -> Option<usize> {
+            Some(self.size)
+        }
+    }
+
+    mod cardinality_swap_test {
+        use super::*;
+
+        #[test]
+        fn test_configure_swaps_to_put_smaller_child_on_the_right() {
+            // Right reports 100 tuples, left only 2: configure() should
+            // swap them so the smaller relation ends up as the (repeatedly
+            // rescanned) right child, while output columns and join results
+            // stay in the caller's original left/right order.
+            let schema = TestTuples::new("").schema.clone();
+            let mut key_expr = ByteCodeExpr::new();
+            key_expr.add_code(ByteCodes::PushField as usize);
+            key_expr.add_code(0);
+
+            let left_tuple = Tuple::new(vec![
+                Field::Int(1),
+                Field::Int(1),
+                Field::Int(1),
+                Field::String("l0".to_string()),
+            ]);
+            let right_tuple = Tuple::new(vec![
+                Field::Int(2),
+                Field::Int(1),
+                Field::Int(1),
+                Field::String("r0".to_string()),
+            ]);
+
+            let mut iter = Box::new(
+                NestedLoopJoin::new(
+                    BooleanOp::Lt,
+                    JoinType::Inner,
+                    key_expr.clone(),
+                    key_expr,
+                    Box::new(SizedIterator::new(vec![left_tuple.clone()], schema.clone(), 2)),
+                    Box::new(SizedIterator::new(vec![right_tuple.clone()], schema.clone(), 100)),
+                    schema,
+                )
+                .unwrap(),
+            );
+            // configure() is where the swap happens.
+            iter.configure(false);
+            let t = execute_iter(&mut *iter, true).unwrap();
+            assert_eq!(t, vec![left_tuple.merge(&right_tuple)]);
+        }
     }
 
     mod opiterator_test {
@@ -305,5 +1020,17 @@ mod test {
             let t_after = execute_iter(&mut *iter, false).unwrap();
             assert_eq!(t_before, t_after);
         }
+
+        #[test]
+        fn test_rewind_with_outer_join() {
+            let (left_expr, right_expr) = get_join_predicate();
+            let mut iter =
+                get_iter_with_join_type(BooleanOp::Eq, JoinType::FullOuter, left_expr, right_expr);
+            iter.configure(true);
+            let t_before = execute_iter(&mut *iter, false).unwrap();
+            iter.rewind().unwrap();
+            let t_after = execute_iter(&mut *iter, false).unwrap();
+            assert_eq!(t_before, t_after);
+        }
     }
 }
@@ -0,0 +1,493 @@
+use super::OpIterator;
+
+use common::bytecode_expr::ByteCodeExpr;
+use common::datatypes::compare_fields;
+use common::{BooleanOp, CrustyError, Field, TableSchema, Tuple};
+
+/// Sort-merge equi-join implementation. Assumes both `left_child` and
+/// `right_child` are already sorted ascending on `left_expr`/`right_expr`
+/// respectively (e.g. coming out of a sort operator or an index scan), and
+/// joins them in a single linear pass instead of hashing (`HashEqJoin`) or
+/// rescanning the right side per left tuple (`NestedLoopJoin`).
+///
+/// Keeps one tuple of lookahead from each child. At each step it compares
+/// the current left and right keys: a smaller left key has no match and
+/// advances the left side, a smaller right key advances the right side,
+/// and on equality the full run of right tuples sharing that key is
+/// buffered into `right_run` and `left.merge(&r)` is emitted for every
+/// tuple in it. The buffer is kept around across left tuples (rather than
+/// cleared as soon as the current left tuple is done with it) so a run of
+/// duplicate left keys only has to scan the matching right run once.
+pub struct SortMergeJoin {
+    // Parameters (No need to reset on close)
+    schema: TableSchema,
+    left_expr: ByteCodeExpr,
+    right_expr: ByteCodeExpr,
+    left_child: Box<dyn OpIterator>,
+    right_child: Box<dyn OpIterator>,
+
+    // States (Need to reset on close)
+    open: bool,
+    left_tuple: Option<Tuple>,
+    right_tuple: Option<Tuple>,
+    /// The buffered run of right tuples whose key equals `right_run_key`.
+    right_run: Vec<Tuple>,
+    right_run_key: Option<Field>,
+    /// Cursor into `right_run` for the left tuple currently being emitted
+    /// against it; `None` means the current left tuple hasn't started (or
+    /// has finished) emitting against the buffered run.
+    run_index: Option<usize>,
+}
+
+impl SortMergeJoin {
+    /// SortMergeJoin constructor. Creates a new node for a sort-merge join.
+    ///
+    /// # Arguments
+    ///
+    /// * `left_expr` - ByteCodeExpr for the left field in join condition.
+    /// * `right_expr` - ByteCodeExpr for the right field in join condition.
+    /// * `left_child` - Left child of join operator; must yield tuples in
+    ///   ascending order of `left_expr`.
+    /// * `right_child` - Right child of join operator; must yield tuples in
+    ///   ascending order of `right_expr`.
+    ///
+    /// Validates `left_expr` and `right_expr` against `schema` up front
+    /// (see [`ByteCodeExpr::validate`]) and confirms they evaluate to
+    /// comparable types, so a malformed predicate is rejected here instead
+    /// of panicking deep inside `eval` once the join is running.
+    pub fn new(
+        schema: TableSchema,
+        left_expr: ByteCodeExpr,
+        right_expr: ByteCodeExpr,
+        left_child: Box<dyn OpIterator>,
+        right_child: Box<dyn OpIterator>,
+    ) -> Result<Self, CrustyError> {
+        let left_type = left_expr.validate(&schema)?;
+        let right_type = right_expr.validate(&schema)?;
+        if left_type != right_type {
+            return Err(CrustyError::CrustyError(format!(
+                "SortMergeJoin predicate type mismatch: left evaluates to {:?}, right evaluates to {:?}",
+                left_type, right_type
+            )));
+        }
+
+        Ok(Self {
+            schema,
+            left_expr,
+            right_expr,
+            left_child,
+            right_child,
+            open: false,
+            left_tuple: None,
+            right_tuple: None,
+            right_run: Vec::new(),
+            right_run_key: None,
+            run_index: None,
+        })
+    }
+}
+
+impl OpIterator for SortMergeJoin {
+    fn configure(&mut self, will_rewind: bool) {
+        self.left_child.configure(will_rewind);
+        self.right_child.configure(will_rewind);
+    }
+
+    fn open(&mut self) -> Result<(), CrustyError> {
+        if self.open {
+            return Ok(());
+        }
+        self.left_child.open()?;
+        self.right_child.open()?;
+        self.left_tuple = self.left_child.next()?;
+        self.right_tuple = self.right_child.next()?;
+        self.open = true;
+        Ok(())
+    }
+
+    fn next(&mut self) -> Result<Option<Tuple>, CrustyError> {
+        if !self.open {
+            panic!("Iterator is not open");
+        }
+        loop {
+            // Walk the buffered run for the left tuple we're currently
+            // emitting matches for, if any.
+            if let Some(idx) = self.run_index {
+                if idx < self.right_run.len() {
+                    let t = self
+                        .left_tuple
+                        .as_ref()
+                        .unwrap()
+                        .merge(&self.right_run[idx]);
+                    self.run_index = Some(idx + 1);
+                    return Ok(Some(t));
+                }
+                self.run_index = None;
+                self.left_tuple = self.left_child.next()?;
+            }
+
+            let left_tuple = match &self.left_tuple {
+                Some(t) => t.clone(),
+                None => return Ok(None),
+            };
+            let left_key = self.left_expr.eval(&left_tuple);
+
+            // A buffered run from a previous left tuple may still apply.
+            if let Some(run_key) = self.right_run_key.clone() {
+                if compare_fields(BooleanOp::Eq, &left_key, &run_key) {
+                    self.run_index = Some(0);
+                    continue;
+                }
+                if compare_fields(BooleanOp::Lt, &left_key, &run_key) {
+                    // This left tuple falls strictly before the buffered
+                    // run's key, so nothing in it (or earlier) can match.
+                    self.left_tuple = self.left_child.next()?;
+                    continue;
+                }
+                // left_key > run_key: the buffered run is now behind the
+                // left side, so drop it and resume scanning the right child.
+                self.right_run.clear();
+                self.right_run_key = None;
+            }
+
+            loop {
+                let right_tuple = match &self.right_tuple {
+                    Some(t) => t.clone(),
+                    // Right side is exhausted, and since both sides are
+                    // sorted ascending, no later left tuple can match either.
+                    None => return Ok(None),
+                };
+                let right_key = self.right_expr.eval(&right_tuple);
+                if compare_fields(BooleanOp::Lt, &right_key, &left_key) {
+                    self.right_tuple = self.right_child.next()?;
+                    continue;
+                }
+                if compare_fields(BooleanOp::Gt, &right_key, &left_key) {
+                    break; // No match for this left tuple.
+                }
+
+                // Equal: buffer the whole run of right tuples sharing this key.
+                self.right_run_key = Some(right_key.clone());
+                self.right_run.clear();
+                while let Some(rt) = self.right_tuple.clone() {
+                    if compare_fields(BooleanOp::Eq, &self.right_expr.eval(&rt), &right_key) {
+                        self.right_run.push(rt);
+                        self.right_tuple = self.right_child.next()?;
+                    } else {
+                        break;
+                    }
+                }
+                self.run_index = Some(0);
+                break;
+            }
+
+            if self.run_index.is_none() {
+                self.left_tuple = self.left_child.next()?;
+            }
+        }
+    }
+
+    fn close(&mut self) -> Result<(), CrustyError> {
+        self.left_child.close()?;
+        self.right_child.close()?;
+        self.open = false;
+        self.left_tuple = None;
+        self.right_tuple = None;
+        self.right_run.clear();
+        self.right_run_key = None;
+        self.run_index = None;
+        Ok(())
+    }
+
+    fn rewind(&mut self) -> Result<(), CrustyError> {
+        self.left_child.rewind()?;
+        self.right_child.rewind()?;
+        self.right_run.clear();
+        self.right_run_key = None;
+        self.run_index = None;
+        self.left_tuple = self.left_child.next()?;
+        self.right_tuple = self.right_child.next()?;
+        Ok(())
+    }
+
+    fn get_schema(&self) -> &TableSchema {
+        &self.schema
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::TupleIterator;
+    use super::*;
+    use crate::testutil::execute_iter;
+    use crate::testutil::TestTuples;
+    use common::bytecode_expr::{ByteCodeExpr, ByteCodes};
+
+    fn get_join_predicate() -> (ByteCodeExpr, ByteCodeExpr) {
+        // Joining two tables each containing the following tuples, already
+        // in ascending order of both join keys:
+        // 1 1 3 E
+        // 2 1 3 G
+        // 3 1 4 A
+        // 4 2 4 G
+        // 5 2 5 G
+        // 6 2 5 G
+
+        // left(col(0) + col(1)) == right(col(2))
+        let mut left = ByteCodeExpr::new();
+        left.add_code(ByteCodes::PushField as usize);
+        left.add_code(0);
+        left.add_code(ByteCodes::PushField as usize);
+        left.add_code(1);
+        left.add_code(ByteCodes::Add as usize);
+
+        let mut right = ByteCodeExpr::new();
+        right.add_code(ByteCodes::PushField as usize);
+        right.add_code(2);
+
+        (left, right)
+    }
+
+    fn get_iter(left_expr: ByteCodeExpr, right_expr: ByteCodeExpr) -> Box<dyn OpIterator> {
+        let setup = TestTuples::new("");
+        let mut iter = Box::new(
+            SortMergeJoin::new(
+                setup.schema.clone(),
+                left_expr,
+                right_expr,
+                Box::new(TupleIterator::new(
+                    setup.tuples.clone(),
+                    setup.schema.clone(),
+                )),
+                Box::new(TupleIterator::new(
+                    setup.tuples.clone(),
+                    setup.schema.clone(),
+                )),
+            )
+            .unwrap(),
+        );
+        iter.configure(false);
+        iter
+    }
+
+    fn run_sort_merge_join(left_expr: ByteCodeExpr, right_expr: ByteCodeExpr) -> Vec<Tuple> {
+        let mut iter = get_iter(left_expr, right_expr);
+        execute_iter(&mut *iter, true).unwrap()
+    }
+
+    mod sort_merge_join_test {
+        use super::*;
+
+        #[test]
+        fn test_empty_predicate_join() {
+            let setup = TestTuples::new("");
+            let left_expr = ByteCodeExpr::new();
+            let right_expr = ByteCodeExpr::new();
+            let res = SortMergeJoin::new(
+                setup.schema.clone(),
+                left_expr,
+                right_expr,
+                Box::new(TupleIterator::new(
+                    setup.tuples.clone(),
+                    setup.schema.clone(),
+                )),
+                Box::new(TupleIterator::new(
+                    setup.tuples.clone(),
+                    setup.schema.clone(),
+                )),
+            );
+            assert!(res.is_err());
+        }
+
+        #[test]
+        fn test_join() {
+            // Joining two tables each containing the following tuples:
+            // 1 1 3 E
+            // 2 1 3 G
+            // 3 1 4 A
+            // 4 2 4 G
+            // 5 2 5 G
+            // 6 2 5 G
+
+            // left(col(0) + col(1)) == right(col(2))
+
+            // Output:
+            // 2 1 3 G 1 1 3 E
+            // 2 1 3 G 2 1 3 G
+            // 3 1 4 A 3 1 4 A
+            // 3 1 4 A 4 2 4 G
+            let (left_expr, right_expr) = get_join_predicate();
+            let t = run_sort_merge_join(left_expr, right_expr);
+            assert_eq!(t.len(), 4);
+            assert_eq!(
+                t[0],
+                Tuple::new(vec![
+                    Field::Int(2),
+                    Field::Int(1),
+                    Field::Int(3),
+                    Field::String("G".to_string()),
+                    Field::Int(1),
+                    Field::Int(1),
+                    Field::Int(3),
+                    Field::String("E".to_string()),
+                ])
+            );
+            assert_eq!(
+                t[1],
+                Tuple::new(vec![
+                    Field::Int(2),
+                    Field::Int(1),
+                    Field::Int(3),
+                    Field::String("G".to_string()),
+                    Field::Int(2),
+                    Field::Int(1),
+                    Field::Int(3),
+                    Field::String("G".to_string()),
+                ])
+            );
+            assert_eq!(
+                t[2],
+                Tuple::new(vec![
+                    Field::Int(3),
+                    Field::Int(1),
+                    Field::Int(4),
+                    Field::String("A".to_string()),
+                    Field::Int(3),
+                    Field::Int(1),
+                    Field::Int(4),
+                    Field::String("A".to_string()),
+                ])
+            );
+            assert_eq!(
+                t[3],
+                Tuple::new(vec![
+                    Field::Int(3),
+                    Field::Int(1),
+                    Field::Int(4),
+                    Field::String("A".to_string()),
+                    Field::Int(4),
+                    Field::Int(2),
+                    Field::Int(4),
+                    Field::String("G".to_string()),
+                ])
+            );
+        }
+
+        #[test]
+        fn test_duplicate_keys_on_both_sides_reuse_buffered_run() {
+            // Both sides sorted on a single int key, with runs of duplicate
+            // keys on *both* the left and right: the buffered right run for
+            // key 1 must be reused across all three matching left tuples
+            // without rescanning the right child.
+            let schema = TestTuples::new("").schema.clone();
+            let left_expr = {
+                let mut e = ByteCodeExpr::new();
+                e.add_code(ByteCodes::PushField as usize);
+                e.add_code(0);
+                e
+            };
+            let right_expr = left_expr.clone();
+
+            let left_tuples = vec![
+                Tuple::new(vec![
+                    Field::Int(1),
+                    Field::Int(1),
+                    Field::Int(1),
+                    Field::String("l0".to_string()),
+                ]),
+                Tuple::new(vec![
+                    Field::Int(1),
+                    Field::Int(1),
+                    Field::Int(1),
+                    Field::String("l1".to_string()),
+                ]),
+                Tuple::new(vec![
+                    Field::Int(1),
+                    Field::Int(1),
+                    Field::Int(1),
+                    Field::String("l2".to_string()),
+                ]),
+                Tuple::new(vec![
+                    Field::Int(2),
+                    Field::Int(1),
+                    Field::Int(1),
+                    Field::String("l3".to_string()),
+                ]),
+            ];
+            let right_tuples = vec![
+                Tuple::new(vec![
+                    Field::Int(1),
+                    Field::Int(1),
+                    Field::Int(1),
+                    Field::String("r0".to_string()),
+                ]),
+                Tuple::new(vec![
+                    Field::Int(1),
+                    Field::Int(1),
+                    Field::Int(1),
+                    Field::String("r1".to_string()),
+                ]),
+            ];
+
+            let mut iter = Box::new(
+                SortMergeJoin::new(
+                    schema.clone(),
+                    left_expr,
+                    right_expr,
+                    Box::new(TupleIterator::new(left_tuples, schema.clone())),
+                    Box::new(TupleIterator::new(right_tuples, schema)),
+                )
+                .unwrap(),
+            );
+            iter.configure(false);
+            let t = execute_iter(&mut *iter, true).unwrap();
+            // 3 left tuples x 2 right tuples sharing key 1; key 2 has no match.
+            assert_eq!(t.len(), 6);
+        }
+    }
+
+    mod opiterator_test {
+        use super::*;
+        #[test]
+        #[should_panic]
+        fn test_next_not_open() {
+            let (left_expr, right_expr) = get_join_predicate();
+            let mut iter = get_iter(left_expr, right_expr);
+            let _ = iter.next();
+        }
+
+        #[test]
+        #[should_panic]
+        fn test_rewind_not_open() {
+            let (left_expr, right_expr) = get_join_predicate();
+            let mut iter = get_iter(left_expr, right_expr);
+            let _ = iter.rewind();
+        }
+
+        #[test]
+        fn test_open() {
+            let (left_expr, right_expr) = get_join_predicate();
+            let mut iter = get_iter(left_expr, right_expr);
+            iter.open().unwrap();
+        }
+
+        #[test]
+        fn test_close() {
+            let (left_expr, right_expr) = get_join_predicate();
+            let mut iter = get_iter(left_expr, right_expr);
+            iter.open().unwrap();
+            iter.close().unwrap();
+        }
+
+        #[test]
+        fn test_rewind() {
+            let (left_expr, right_expr) = get_join_predicate();
+            let mut iter = get_iter(left_expr, right_expr);
+            iter.configure(true);
+            let t_before = execute_iter(&mut *iter, false).unwrap();
+            iter.rewind().unwrap();
+            let t_after = execute_iter(&mut *iter, false).unwrap();
+            assert_eq!(t_before, t_after);
+        }
+    }
+}
@@ -0,0 +1,311 @@
+use crate::page::Page;
+use common::prelude::*;
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+/// Default number of pages `BufferPool` holds before it starts evicting.
+pub(crate) const DEFAULT_BUFFER_POOL_CAPACITY: usize = 256;
+
+/// A single cached page and the bookkeeping a clock/second-chance policy
+/// needs to decide whether it is safe to reclaim.
+struct Frame {
+    key: (ContainerId, PageId),
+    page: Page,
+    dirty: bool,
+    pin_count: u32,
+    /// Set on every access; cleared (not evicted) the first time the clock
+    /// hand sweeps past it.
+    referenced: bool,
+}
+
+struct Inner {
+    /// Fixed-size frame table; `None` marks a never-yet-used slot.
+    slots: Vec<Option<Frame>>,
+    index: HashMap<(ContainerId, PageId), usize>,
+    /// Circular sweep pointer for the clock eviction policy.
+    clock_hand: usize,
+}
+
+impl Inner {
+    /// Find a slot to hold a newly loaded page: an empty slot if one
+    /// exists, otherwise the first unpinned frame the clock hand finds with
+    /// its reference bit already cleared, flushing it first if dirty.
+    /// Frames with the reference bit set get a second chance (bit cleared,
+    /// hand moves on) before they're eligible for eviction.
+    fn find_or_evict(
+        &mut self,
+        flush: &impl Fn(ContainerId, &Page) -> Result<(), CrustyError>,
+    ) -> Result<usize, CrustyError> {
+        let capacity = self.slots.len();
+        for _ in 0..2 * capacity {
+            let idx = self.clock_hand;
+            self.clock_hand = (self.clock_hand + 1) % capacity;
+            match &mut self.slots[idx] {
+                None => return Ok(idx),
+                Some(frame) => {
+                    if frame.pin_count > 0 {
+                        continue;
+                    }
+                    if frame.referenced {
+                        frame.referenced = false;
+                        continue;
+                    }
+                    if frame.dirty {
+                        flush(frame.key.0, &frame.page)?;
+                    }
+                    self.index.remove(&frame.key);
+                    return Ok(idx);
+                }
+            }
+        }
+        Err(CrustyError::CrustyError(
+            "BufferPool exhausted: every frame is pinned".to_string(),
+        ))
+    }
+}
+
+/// Fixed-capacity page cache keyed on `(ContainerId, PageId)`, sitting in
+/// front of `HeapFile` so `StorageManager::get_page`/`write_page` serve
+/// repeated access to the same page from memory instead of reopening and
+/// rereading the heap file on every call.
+///
+/// Callers supply the actual disk I/O as closures (`load` on a miss,
+/// `flush` when a dirty frame is evicted or the pool is cleared) so this
+/// type stays decoupled from how `StorageManager` maps a `ContainerId` to
+/// its backing `HeapFile`.
+pub(crate) struct BufferPool {
+    inner: Mutex<Inner>,
+}
+
+impl Default for BufferPool {
+    fn default() -> Self {
+        Self::new(DEFAULT_BUFFER_POOL_CAPACITY)
+    }
+}
+
+impl BufferPool {
+    pub(crate) fn new(capacity: usize) -> Self {
+        Self {
+            inner: Mutex::new(Inner {
+                slots: (0..capacity).map(|_| None).collect(),
+                index: HashMap::new(),
+                clock_hand: 0,
+            }),
+        }
+    }
+
+    /// Return a copy of the cached page for `(container_id, page_id)`,
+    /// calling `load` on a miss. `load` returning `None` (page does not
+    /// exist) is not cached. When `pin` is set the frame's pin count is
+    /// incremented, making it ineligible for eviction until a matching
+    /// [`BufferPool::unpin`] call.
+    pub(crate) fn get_page(
+        &self,
+        container_id: ContainerId,
+        page_id: PageId,
+        pin: bool,
+        load: impl FnOnce() -> Option<Page>,
+        flush: impl Fn(ContainerId, &Page) -> Result<(), CrustyError>,
+    ) -> Result<Option<Page>, CrustyError> {
+        let key = (container_id, page_id);
+        let mut inner = self.inner.lock().unwrap();
+        if let Some(&idx) = inner.index.get(&key) {
+            let frame = inner.slots[idx].as_mut().unwrap();
+            frame.referenced = true;
+            if pin {
+                frame.pin_count += 1;
+            }
+            return Ok(Some(frame.page.clone()));
+        }
+        // Don't hold the pool locked while doing disk I/O on a miss.
+        drop(inner);
+
+        let page = match load() {
+            Some(page) => page,
+            None => return Ok(None),
+        };
+
+        let mut inner = self.inner.lock().unwrap();
+        // Another thread may have loaded the same page while we didn't
+        // hold the lock; if so just use what's already cached.
+        if let Some(&idx) = inner.index.get(&key) {
+            let frame = inner.slots[idx].as_mut().unwrap();
+            frame.referenced = true;
+            if pin {
+                frame.pin_count += 1;
+            }
+            return Ok(Some(frame.page.clone()));
+        }
+
+        let idx = inner.find_or_evict(&flush)?;
+        let result = page.clone();
+        inner.slots[idx] = Some(Frame {
+            key,
+            page,
+            dirty: false,
+            pin_count: if pin { 1 } else { 0 },
+            referenced: true,
+        });
+        inner.index.insert(key, idx);
+        Ok(Some(result))
+    }
+
+    /// Cache `page`, marking its frame dirty instead of writing through to
+    /// disk immediately. Evicts (flushing first if dirty) to make room when
+    /// `page`'s container/page pair isn't already cached and the pool is
+    /// full.
+    pub(crate) fn put_page(
+        &self,
+        container_id: ContainerId,
+        page: &Page,
+        flush: impl Fn(ContainerId, &Page) -> Result<(), CrustyError>,
+    ) -> Result<(), CrustyError> {
+        let key = (container_id, page.get_page_id());
+        let mut inner = self.inner.lock().unwrap();
+        if let Some(&idx) = inner.index.get(&key) {
+            let frame = inner.slots[idx].as_mut().unwrap();
+            frame.page = page.clone();
+            frame.dirty = true;
+            frame.referenced = true;
+            return Ok(());
+        }
+
+        let idx = inner.find_or_evict(&flush)?;
+        inner.slots[idx] = Some(Frame {
+            key,
+            page: page.clone(),
+            dirty: true,
+            pin_count: 0,
+            referenced: true,
+        });
+        inner.index.insert(key, idx);
+        Ok(())
+    }
+
+    /// Release a pin taken by a [`BufferPool::get_page`] call with
+    /// `pin = true`. A no-op if the page is no longer cached.
+    pub(crate) fn unpin(&self, container_id: ContainerId, page_id: PageId) {
+        let mut inner = self.inner.lock().unwrap();
+        if let Some(&idx) = inner.index.get(&(container_id, page_id)) {
+            if let Some(frame) = inner.slots[idx].as_mut() {
+                frame.pin_count = frame.pin_count.saturating_sub(1);
+            }
+        }
+    }
+
+    /// Flush every dirty frame (via `flush`) and empty the pool.
+    pub(crate) fn clear(
+        &self,
+        flush: impl Fn(ContainerId, &Page) -> Result<(), CrustyError>,
+    ) -> Result<(), CrustyError> {
+        let mut inner = self.inner.lock().unwrap();
+        for slot in inner.slots.iter_mut() {
+            if let Some(frame) = slot.take() {
+                if frame.dirty {
+                    flush(frame.key.0, &frame.page)?;
+                }
+            }
+        }
+        inner.index.clear();
+        inner.clock_hand = 0;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn noop_flush(_container_id: ContainerId, _page: &Page) -> Result<(), CrustyError> {
+        Ok(())
+    }
+
+    #[test]
+    fn bp_caches_loaded_page_and_avoids_reloading() {
+        let pool = BufferPool::new(2);
+        let mut loads = 0;
+        let page = pool
+            .get_page(
+                1,
+                0,
+                false,
+                || {
+                    loads += 1;
+                    Some(Page::new(0))
+                },
+                noop_flush,
+            )
+            .unwrap();
+        assert!(page.is_some());
+        assert_eq!(loads, 1);
+
+        // Second access should hit the cache, not call `load` again.
+        let page2 = pool
+            .get_page(
+                1,
+                0,
+                false,
+                || {
+                    loads += 1;
+                    Some(Page::new(0))
+                },
+                noop_flush,
+            )
+            .unwrap();
+        assert!(page2.is_some());
+        assert_eq!(loads, 1);
+    }
+
+    #[test]
+    fn bp_evicts_unpinned_frame_when_full() {
+        let pool = BufferPool::new(1);
+        pool.get_page(1, 0, false, || Some(Page::new(0)), noop_flush)
+            .unwrap();
+        // Capacity is 1, so loading a second page must evict the first.
+        pool.get_page(1, 1, false, || Some(Page::new(1)), noop_flush)
+            .unwrap();
+
+        let mut reloaded = false;
+        pool.get_page(
+            1,
+            0,
+            false,
+            || {
+                reloaded = true;
+                Some(Page::new(0))
+            },
+            noop_flush,
+        )
+        .unwrap();
+        assert!(reloaded, "evicted page should require a reload");
+    }
+
+    #[test]
+    fn bp_does_not_evict_pinned_frame() {
+        let pool = BufferPool::new(1);
+        pool.get_page(1, 0, true, || Some(Page::new(0)), noop_flush)
+            .unwrap();
+
+        // The only frame is pinned, so there's nowhere to evict to.
+        let err = pool.get_page(1, 1, false, || Some(Page::new(1)), noop_flush);
+        assert!(err.is_err());
+
+        pool.unpin(1, 0);
+        let ok = pool.get_page(1, 1, false, || Some(Page::new(1)), noop_flush);
+        assert!(ok.is_ok());
+    }
+
+    #[test]
+    fn bp_put_page_marks_dirty_and_clear_flushes() {
+        let pool = BufferPool::new(2);
+        pool.put_page(1, &Page::new(0), noop_flush).unwrap();
+
+        let flushed = Mutex::new(Vec::new());
+        pool.clear(|container_id, page| {
+            flushed.lock().unwrap().push((container_id, page.get_page_id()));
+            Ok(())
+        })
+        .unwrap();
+        assert_eq!(*flushed.lock().unwrap(), vec![(1, 0)]);
+    }
+}
@@ -0,0 +1,247 @@
+use crate::heapfile::HeapFile;
+use crate::page::Page;
+use common::prelude::*;
+use common::PAGE_SIZE;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use std::io::{Read, Write};
+use std::path::Path;
+
+/// Magic bytes opening a compressed archive file; see [`ArchiveBackend`].
+const ARCHIVE_MAGIC: u32 = 0x4152_4348; // written little-endian: the bytes "HCRA"
+/// Size of the archive's file-level header: magic(4) + page count(4).
+const ARCHIVE_HEADER_SIZE: usize = 8;
+
+/// Abstracts where a container's pages actually live, so `StorageManager`
+/// doesn't have to hard-code [`HeapFile`] as the only possible backing store
+/// for a container. [`ArchiveBackend`] is the other implementation: reading
+/// through it keeps a container's pages compressed on disk instead of
+/// rehydrating them into an ordinary heap file the moment the container is
+/// registered.
+pub(crate) trait ContainerBackend: Send + Sync {
+    /// Read the page stored at `page_id`.
+    fn read_page(&self, page_id: PageId) -> Result<Page, CrustyError>;
+    /// Write `page` to this backend. Backends that are read-only (e.g.
+    /// [`ArchiveBackend`]) always return an error.
+    fn write_page(&self, page: &Page) -> Result<(), CrustyError>;
+    /// The number of pages currently stored.
+    fn num_pages(&self) -> PageId;
+    /// Iterate every page currently stored, in ascending `PageId` order.
+    fn iter(&self) -> Box<dyn Iterator<Item = Page> + '_>;
+}
+
+impl ContainerBackend for HeapFile {
+    fn read_page(&self, page_id: PageId) -> Result<Page, CrustyError> {
+        self.read_page_from_file(page_id)
+    }
+
+    fn write_page(&self, page: &Page) -> Result<(), CrustyError> {
+        self.write_page_to_file(page)
+    }
+
+    fn num_pages(&self) -> PageId {
+        HeapFile::num_pages(self)
+    }
+
+    fn iter(&self) -> Box<dyn Iterator<Item = Page> + '_> {
+        Box::new((0..self.num_pages()).filter_map(move |page_id| {
+            self.read_page_from_file(page_id).ok()
+        }))
+    }
+}
+
+/// A read-only container backend that stores its pages gzip-compressed,
+/// one page per entry, for cold data that's written once (e.g. by an
+/// offline export job) and only ever read afterwards. Counterpart to
+/// [`crate::heapfile::HeapFile`], which is always read/write; a container
+/// registered against an `ArchiveBackend` (see
+/// `StorageManager::create_archive_container`) rejects every mutation.
+///
+/// Each page is compressed independently rather than the whole archive as
+/// one stream, so reading page `n` only ever has to decompress page `n`.
+pub(crate) struct ArchiveBackend {
+    /// The whole archive file, kept in memory; pages are decompressed out
+    /// of this on demand rather than eagerly at `open` time.
+    bytes: Vec<u8>,
+    /// Byte range within `bytes` of each page's compressed form, in page
+    /// id order, built once by [`ArchiveBackend::open`].
+    page_ranges: Vec<(usize, usize)>,
+}
+
+impl ArchiveBackend {
+    /// Write a new compressed archive to `path` holding `pages`, indexed by
+    /// their position in the slice (so callers should pass them in
+    /// ascending `PageId` order).
+    pub(crate) fn build(path: &Path, pages: &[Page]) -> Result<(), CrustyError> {
+        let mut out = Vec::new();
+        out.extend_from_slice(&ARCHIVE_MAGIC.to_le_bytes());
+        out.extend_from_slice(&(pages.len() as u32).to_le_bytes());
+        for page in pages {
+            let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+            encoder.write_all(page.to_bytes().as_ref()).map_err(|e| {
+                CrustyError::CrustyError(format!("Could not compress archive page: {}", e))
+            })?;
+            let compressed = encoder.finish().map_err(|e| {
+                CrustyError::CrustyError(format!("Could not compress archive page: {}", e))
+            })?;
+            out.extend_from_slice(&(compressed.len() as u32).to_le_bytes());
+            out.extend_from_slice(&compressed);
+        }
+        std::fs::write(path, out)
+            .map_err(|e| CrustyError::CrustyError(format!("Could not write archive: {}", e)))
+    }
+
+    /// Open an existing archive and index where each page's compressed
+    /// bytes live, without decompressing any of them yet.
+    pub(crate) fn open(path: &Path) -> Result<Self, CrustyError> {
+        let bytes = std::fs::read(path)
+            .map_err(|e| CrustyError::CrustyError(format!("Could not read archive: {}", e)))?;
+        if bytes.len() < ARCHIVE_HEADER_SIZE || bytes[0..4] != ARCHIVE_MAGIC.to_le_bytes() {
+            return Err(CrustyError::CrustyError(
+                "Not a valid compressed archive".to_string(),
+            ));
+        }
+        let count = u32::from_le_bytes(bytes[4..8].try_into().unwrap()) as usize;
+
+        let mut page_ranges = Vec::with_capacity(count);
+        let mut offset = ARCHIVE_HEADER_SIZE;
+        for _ in 0..count {
+            let len_bytes = bytes
+                .get(offset..offset + 4)
+                .ok_or_else(|| CrustyError::CrustyError("Truncated archive".to_string()))?;
+            let len = u32::from_le_bytes(len_bytes.try_into().unwrap()) as usize;
+            offset += 4;
+            if offset + len > bytes.len() {
+                return Err(CrustyError::CrustyError("Truncated archive".to_string()));
+            }
+            page_ranges.push((offset, offset + len));
+            offset += len;
+        }
+
+        Ok(Self { bytes, page_ranges })
+    }
+
+    /// Decompress and return the page stored at `page_id`.
+    pub(crate) fn read_page(&self, page_id: PageId) -> Result<Page, CrustyError> {
+        let (start, end) = self
+            .page_ranges
+            .get(page_id as usize)
+            .ok_or_else(|| CrustyError::CrustyError(format!("Page {} does not exist", page_id)))?;
+        let mut decoder = GzDecoder::new(&self.bytes[*start..*end]);
+        let mut raw = Vec::with_capacity(PAGE_SIZE);
+        decoder.read_to_end(&mut raw).map_err(|e| {
+            CrustyError::CrustyError(format!("Could not decompress archive page: {}", e))
+        })?;
+        let data: [u8; PAGE_SIZE] = raw.try_into().map_err(|_| {
+            CrustyError::CrustyError("Decompressed archive page has the wrong size".to_string())
+        })?;
+        // Archives are read-only and built once, with no file-level
+        // checksum toggle of their own (unlike `HeapFile`), so always
+        // verify the page's embedded CRC32C here.
+        Page::from_bytes_checked(data)
+    }
+
+    /// The number of pages in this archive.
+    pub(crate) fn num_pages(&self) -> PageId {
+        self.page_ranges.len() as PageId
+    }
+}
+
+impl ContainerBackend for ArchiveBackend {
+    fn read_page(&self, page_id: PageId) -> Result<Page, CrustyError> {
+        ArchiveBackend::read_page(self, page_id)
+    }
+
+    fn write_page(&self, _page: &Page) -> Result<(), CrustyError> {
+        Err(CrustyError::CrustyError(
+            "Cannot write to a read-only archive container".to_string(),
+        ))
+    }
+
+    fn num_pages(&self) -> PageId {
+        ArchiveBackend::num_pages(self)
+    }
+
+    fn iter(&self) -> Box<dyn Iterator<Item = Page> + '_> {
+        Box::new((0..self.num_pages()).filter_map(move |page_id| {
+            ArchiveBackend::read_page(self, page_id).ok()
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use common::testutil::gen_random_test_sm_dir;
+
+    #[test]
+    fn archive_round_trips_pages_through_build_and_open() {
+        let dir = gen_random_test_sm_dir();
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("archive.bin");
+
+        let mut page0 = Page::new(0);
+        let mut page1 = Page::new(1);
+        {
+            use crate::heap_page::HeapPage;
+            page0.add_value(b"hello");
+            page1.add_value(b"world");
+        }
+
+        ArchiveBackend::build(&path, &[page0, page1]).unwrap();
+        let archive = ArchiveBackend::open(&path).unwrap();
+
+        assert_eq!(archive.num_pages(), 2);
+        assert_eq!(archive.read_page(0).unwrap().get_page_id(), 0);
+        assert_eq!(archive.read_page(1).unwrap().get_page_id(), 1);
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn archive_read_page_out_of_range_errors() {
+        let dir = gen_random_test_sm_dir();
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("archive.bin");
+
+        ArchiveBackend::build(&path, &[Page::new(0)]).unwrap();
+        let archive = ArchiveBackend::open(&path).unwrap();
+
+        assert!(archive.read_page(1).is_err());
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn archive_backend_reads_lazily_through_container_backend_trait() {
+        let dir = gen_random_test_sm_dir();
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("archive.bin");
+
+        let mut page0 = Page::new(0);
+        {
+            use crate::heap_page::HeapPage;
+            page0.add_value(b"lazy");
+        }
+        ArchiveBackend::build(&path, &[page0]).unwrap();
+
+        let backend: Box<dyn ContainerBackend> = Box::new(ArchiveBackend::open(&path).unwrap());
+        assert_eq!(backend.num_pages(), 1);
+        assert_eq!(backend.read_page(0).unwrap().get_page_id(), 0);
+        assert_eq!(backend.iter().count(), 1);
+        assert!(backend.write_page(&Page::new(0)).is_err());
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn archive_open_rejects_non_archive_file() {
+        let dir = gen_random_test_sm_dir();
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("not_an_archive.bin");
+        std::fs::write(&path, b"not an archive").unwrap();
+
+        assert!(ArchiveBackend::open(&path).is_err());
+        std::fs::remove_dir_all(&dir).ok();
+    }
+}
@@ -0,0 +1,292 @@
+use common::prelude::*;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::sync::RwLock;
+
+/// SHA-256 digest of a stored value's bytes, used to key the dedup index.
+pub(crate) type ContentDigest = [u8; 32];
+
+/// Hash `bytes` in a single pass (no extra copy) to get the digest used to
+/// look up or record an entry in [`DedupIndex`].
+pub(crate) fn digest_value(bytes: &[u8]) -> ContentDigest {
+    let mut hasher = Sha256::new();
+    hasher.update(bytes);
+    hasher.finalize().into()
+}
+
+#[derive(Clone, Copy)]
+enum DedupEntry {
+    /// A digest claimed by `lookup_or_reserve` but not yet backed by a
+    /// physical value -- the inserter holding the claim is still off doing
+    /// the page write; a concurrent insert of the same bytes must not also
+    /// claim it (that's the race `lookup_or_reserve`/`record` used to have),
+    /// but also can't be handed a `ValueId` that doesn't exist yet.
+    Reserved,
+    Live { value_id: ValueId, ref_count: u32 },
+}
+
+/// Result of [`DedupIndex::lookup_or_reserve`].
+pub(crate) enum DedupLookup {
+    /// `digest` already has a live value; its ref count was bumped.
+    Found(ValueId),
+    /// `digest` is mid-insert on another thread; the caller should retry
+    /// the lookup rather than insert a second copy.
+    Reserved,
+    /// `digest` was unclaimed and now belongs to the caller, who must
+    /// physically insert the value and call [`DedupIndex::record`].
+    Claimed,
+}
+
+/// One digest's persisted dedup state, with the digest hex-encoded so the
+/// record serializes as plain JSON; the index is persisted as a JSON array
+/// of these.
+#[derive(Serialize, Deserialize, Clone)]
+pub(crate) struct DedupRecord {
+    container_id: ContainerId,
+    digest_hex: String,
+    value_id: ValueId,
+    ref_count: u32,
+}
+
+/// Per-container content-addressed index mapping a [`ContentDigest`] to the
+/// `ValueId` that already holds those bytes, so `StorageManager::insert_value`
+/// can skip writing an identical payload a second time and instead hand back
+/// the existing id. Reference-counted so `StorageManager::delete_value` only
+/// frees the underlying slot once the last id pointing at a digest goes
+/// away.
+#[derive(Default)]
+pub(crate) struct DedupIndex {
+    by_digest: RwLock<HashMap<ContainerId, HashMap<ContentDigest, DedupEntry>>>,
+    /// Reverse index so `release` can find a value's digest from its
+    /// `ValueId` alone, since `delete_value` is never given the original
+    /// bytes back.
+    by_value_id: RwLock<HashMap<ValueId, ContentDigest>>,
+}
+
+impl DedupIndex {
+    pub(crate) fn new() -> Self {
+        Self::default()
+    }
+
+    /// If `digest` is already recorded for `container_id`, bump its
+    /// reference count and return the `ValueId` it lives at. Otherwise,
+    /// atomically claims `digest` for the caller (so a concurrent call for
+    /// the same digest can't also claim it) and returns `Claimed`, meaning
+    /// the caller should insert normally and call [`DedupIndex::record`] --
+    /// or, if another caller already holds the claim, `Reserved`, meaning
+    /// the caller should retry once that insert finishes.
+    pub(crate) fn lookup_or_reserve(
+        &self,
+        container_id: ContainerId,
+        digest: ContentDigest,
+    ) -> DedupLookup {
+        let mut by_digest = self.by_digest.write().unwrap();
+        let container_entries = by_digest.entry(container_id).or_default();
+        match container_entries.get_mut(&digest) {
+            Some(DedupEntry::Live { value_id, ref_count }) => {
+                *ref_count += 1;
+                DedupLookup::Found(*value_id)
+            }
+            Some(DedupEntry::Reserved) => DedupLookup::Reserved,
+            None => {
+                container_entries.insert(digest, DedupEntry::Reserved);
+                DedupLookup::Claimed
+            }
+        }
+    }
+
+    /// Record that `digest` now lives at `value_id` for the first time,
+    /// resolving the `Reserved` claim `lookup_or_reserve` handed back.
+    pub(crate) fn record(&self, container_id: ContainerId, digest: ContentDigest, value_id: ValueId) {
+        self.by_digest
+            .write()
+            .unwrap()
+            .entry(container_id)
+            .or_default()
+            .insert(digest, DedupEntry::Live { value_id, ref_count: 1 });
+        self.by_value_id.write().unwrap().insert(value_id, digest);
+    }
+
+    /// Drop one reference to whatever digest `value_id` is tracked under.
+    /// Returns `true` if the caller's normal delete path should now run
+    /// (either the last reference was just dropped, or `value_id` was never
+    /// tracked here, meaning dedup was off when it was inserted), or `false`
+    /// if other ids still reference the same bytes and the delete should be
+    /// a no-op.
+    pub(crate) fn release(&self, container_id: ContainerId, value_id: ValueId) -> bool {
+        let digest = match self.by_value_id.read().unwrap().get(&value_id).copied() {
+            Some(digest) => digest,
+            None => return true,
+        };
+
+        let mut by_digest = self.by_digest.write().unwrap();
+        let still_referenced = match by_digest.get_mut(&container_id) {
+            Some(container_entries) => match container_entries.get_mut(&digest) {
+                Some(DedupEntry::Live { ref_count, .. }) => {
+                    *ref_count -= 1;
+                    if *ref_count == 0 {
+                        container_entries.remove(&digest);
+                        false
+                    } else {
+                        true
+                    }
+                }
+                Some(DedupEntry::Reserved) | None => false,
+            },
+            None => false,
+        };
+        drop(by_digest);
+
+        if !still_referenced {
+            self.by_value_id.write().unwrap().remove(&value_id);
+        }
+        !still_referenced
+    }
+
+    /// Clear all tracked state, for `StorageManager::reset`.
+    pub(crate) fn clear(&self) {
+        self.by_digest.write().unwrap().clear();
+        self.by_value_id.write().unwrap().clear();
+    }
+
+    /// Flatten the index into records suitable for persisting alongside
+    /// `container_to_hf.json`. A digest still `Reserved` (its insert never
+    /// got to call `record`, e.g. the process crashed mid-insert) has no
+    /// `ValueId` to persist and is dropped rather than snapshotted.
+    pub(crate) fn snapshot(&self) -> Vec<DedupRecord> {
+        self.by_digest
+            .read()
+            .unwrap()
+            .iter()
+            .flat_map(|(container_id, entries)| {
+                entries.iter().filter_map(move |(digest, entry)| match entry {
+                    DedupEntry::Live { value_id, ref_count } => Some(DedupRecord {
+                        container_id: *container_id,
+                        digest_hex: to_hex(digest),
+                        value_id: *value_id,
+                        ref_count: *ref_count,
+                    }),
+                    DedupEntry::Reserved => None,
+                })
+            })
+            .collect()
+    }
+
+    /// Rebuild an index from records written by a previous [`DedupIndex::snapshot`].
+    pub(crate) fn restore(records: Vec<DedupRecord>) -> Self {
+        let index = Self::new();
+        let mut by_digest = index.by_digest.write().unwrap();
+        let mut by_value_id = index.by_value_id.write().unwrap();
+        for record in records {
+            if let Some(digest) = from_hex(&record.digest_hex) {
+                by_digest.entry(record.container_id).or_default().insert(
+                    digest,
+                    DedupEntry::Live {
+                        value_id: record.value_id,
+                        ref_count: record.ref_count,
+                    },
+                );
+                by_value_id.insert(record.value_id, digest);
+            }
+        }
+        drop(by_digest);
+        drop(by_value_id);
+        index
+    }
+}
+
+fn to_hex(digest: &ContentDigest) -> String {
+    digest.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn from_hex(s: &str) -> Option<ContentDigest> {
+    if s.len() != 64 {
+        return None;
+    }
+    let mut out = [0u8; 32];
+    for (i, byte) in out.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(&s[i * 2..i * 2 + 2], 16).ok()?;
+    }
+    Some(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vid(slot: SlotId) -> ValueId {
+        ValueId {
+            container_id: 1,
+            segment_id: None,
+            page_id: Some(0),
+            slot_id: Some(slot),
+        }
+    }
+
+    #[test]
+    fn dedup_second_insert_of_same_digest_reuses_value_id() {
+        let index = DedupIndex::new();
+        let digest = digest_value(b"hello");
+        assert!(matches!(
+            index.lookup_or_reserve(1, digest),
+            DedupLookup::Claimed
+        ));
+        index.record(1, digest, vid(0));
+        assert!(matches!(
+            index.lookup_or_reserve(1, digest),
+            DedupLookup::Found(found) if found == vid(0)
+        ));
+    }
+
+    #[test]
+    fn dedup_concurrent_claim_of_same_digest_is_told_to_retry() {
+        let index = DedupIndex::new();
+        let digest = digest_value(b"hello");
+        assert!(matches!(
+            index.lookup_or_reserve(1, digest),
+            DedupLookup::Claimed
+        ));
+        // A second inserter racing on the same bytes must not also be able
+        // to claim the digest while the first insert is still in flight.
+        assert!(matches!(
+            index.lookup_or_reserve(1, digest),
+            DedupLookup::Reserved
+        ));
+        index.record(1, digest, vid(0));
+        assert!(matches!(
+            index.lookup_or_reserve(1, digest),
+            DedupLookup::Found(found) if found == vid(0)
+        ));
+    }
+
+    #[test]
+    fn dedup_release_only_frees_slot_on_last_reference() {
+        let index = DedupIndex::new();
+        let digest = digest_value(b"hello");
+        index.record(1, digest, vid(0));
+        index.lookup_or_reserve(1, digest); // second logical reference
+
+        assert!(!index.release(1, vid(0)));
+        assert!(index.release(1, vid(0)));
+    }
+
+    #[test]
+    fn dedup_release_of_untracked_value_id_signals_normal_delete() {
+        let index = DedupIndex::new();
+        assert!(index.release(1, vid(0)));
+    }
+
+    #[test]
+    fn dedup_snapshot_round_trips_through_restore() {
+        let index = DedupIndex::new();
+        let digest = digest_value(b"hello");
+        index.record(1, digest, vid(0));
+        index.lookup_or_reserve(1, digest);
+
+        let restored = DedupIndex::restore(index.snapshot());
+        assert!(matches!(
+            restored.lookup_or_reserve(1, digest),
+            DedupLookup::Found(found) if found == vid(0)
+        ));
+    }
+}
@@ -2,22 +2,282 @@ use crate::page;
 use crate::page::{Offset, Page};
 use common::prelude::*;
 use common::PAGE_SIZE;
+use flate2::read::ZlibDecoder;
+use flate2::write::ZlibEncoder;
+use flate2::Compression;
 use std::fmt;
-use std::fmt::Write;
+use std::fmt::Write as _;
+use std::io::{Read, Write as _};
 
 // Add any other constants, type aliases, or structs, or definitions here
-const META_HEADER_SIZE: usize = 8; // size of the page metadata
 const SLOT_META_SIZE: usize = 6; // size of the slot metadata (per slot)
 const PAGEID_SIZE: usize = std::mem::size_of::<PageId>(); // size of PageId type
 const OFFSET_SIZE: usize = std::mem::size_of::<Offset>(); // size of Offset type
 const SLOTID_SIZE: usize = std::mem::size_of::<SlotId>(); // size of SlotId type
 
+// A slot's stored byte count never needs the top bits of its 16-bit "size"
+// field (no value comes close to 2^14 bytes on a PAGE_SIZE page), so two of
+// those bits double as flags instead of growing SLOT_META_SIZE: one marks
+// the slot's bytes as LZ4-compressed (see `add_value`'s compress-if-it-
+// shrinks policy), the other marks them as a `BlobRedirect` rather than an
+// inline value (see `Page::add_large_value`).
+const SLOT_COMPRESSED_FLAG: u16 = 1 << 15;
+const SLOT_LARGE_VALUE_FLAG: u16 = 1 << 14;
+const SLOT_SIZE_MASK: u16 = !(SLOT_COMPRESSED_FLAG | SLOT_LARGE_VALUE_FLAG);
+
 // locations of the header values (to allow for easy access)
 const PAGEID_LOC: usize = 0;
 const NUMSLOTS_LOC: usize = PAGEID_LOC + PAGEID_SIZE;
 const FIRSTOFFSET_LOC: usize = NUMSLOTS_LOC + std::mem::size_of::<u16>();
 const TOTSLOTS_LOC: usize = FIRSTOFFSET_LOC + OFFSET_SIZE;
-const SLOTSTART_LOC: usize = TOTSLOTS_LOC + SLOTID_SIZE;
+pub(crate) const FREELIST_HEAD_LOC: usize = TOTSLOTS_LOC + SLOTID_SIZE;
+// one-byte HEAP_PAGE/BLOB_PAGE tag (see `PageType`), read before anything
+// else in the header is interpreted as a slot directory
+pub(crate) const PAGETYPE_LOC: usize = FREELIST_HEAD_LOC + SLOTID_SIZE;
+// running total of bytes sitting in deleted-but-not-yet-compacted slots
+// inside the data region (see `delete_value`/`compact`)
+const FRAGMENTED_LOC: usize = PAGETYPE_LOC + 1;
+const SLOTSTART_LOC: usize = FRAGMENTED_LOC + std::mem::size_of::<u16>();
+// one-byte id naming the `Compressor` (see `compressor_for_id`) that
+// produced this page's on-disk bytes, read by `Page::from_bytes` before it
+// inflates the packed-record region back out
+pub(crate) const COMPRESSOR_ID_LOC: usize = SLOTSTART_LOC;
+// fixed-size Bloom filter bit array summarizing every value currently added
+// via `add_value` (see `BLOOM_BITS`/`may_contain`)
+pub(crate) const BLOOM_LOC: usize = COMPRESSOR_ID_LOC + 1;
+/// Size, in bits, of the page-level Bloom filter embedded at `BLOOM_LOC`.
+/// Picked for m/n ~= 8 and k ~= 6 (`k = (m/n) * ln 2`) against a page
+/// holding on the order of 32 small-ish values, which puts the
+/// false-positive rate around 2%.
+const BLOOM_BITS: usize = 256;
+const BLOOM_BYTES: usize = BLOOM_BITS / 8;
+/// Number of independent hash probes per value, derived from `BLOOM_BITS`
+/// above via double hashing (see `bloom_bit_positions`) rather than k
+/// independent hash functions.
+const BLOOM_K: u64 = 6;
+
+// CRC32C checksum over the rest of the page's on-disk bytes (this field
+// itself excluded), written by `Page::to_bytes` and verified by
+// `Page::from_bytes_checked` -- see those for why a page also checksummed
+// at the `HeapFile` layer still benefits from one here.
+pub(crate) const CHECKSUM_LOC: usize = BLOOM_LOC + BLOOM_BYTES;
+pub(crate) const CHECKSUM_SIZE: usize = 4;
+
+// size of the page metadata (grew by SLOTID_SIZE to fit FREELIST_HEAD_LOC,
+// by 1 byte to fit PAGETYPE_LOC, by 2 bytes to fit FRAGMENTED_LOC, by
+// 1 byte to fit COMPRESSOR_ID_LOC, by BLOOM_BYTES to fit BLOOM_LOC, and by
+// CHECKSUM_SIZE to fit CHECKSUM_LOC)
+pub(crate) const META_HEADER_SIZE: usize = CHECKSUM_LOC + CHECKSUM_SIZE;
+
+/// Sentinel stored as a freelist "next" pointer (including the head, in the
+/// page meta header) meaning "no more free slots" -- the chain ends here.
+/// Slot ids are reused in LIFO order off of this intrusive free list rather
+/// than always handing out the lowest available id, so `get_next_slotid` is
+/// O(1) instead of a linear scan-and-sort of every slot header.
+pub(crate) const FREELIST_NIL: SlotId = SlotId::MAX;
+
+/// Distinguishes an ordinary slotted page from a `BLOB_PAGE` holding one
+/// chunk of an oversized value's chain (see the blob-chaining `impl Page`
+/// block further down) or a `PREFIX_PAGE` holding shared-prefix-compressed
+/// sorted entries (see `Page::new_prefix_compressed`). Stored as a single
+/// byte at `PAGETYPE_LOC` so `fsck` and the page iterator can tell which
+/// layout a page's bytes are in before trying to interpret a slot
+/// directory that, on a `BLOB_PAGE`/`PREFIX_PAGE`, isn't there.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub(crate) enum PageType {
+    Heap = 0,
+    Blob = 1,
+    Prefix = 2,
+}
+
+impl Page {
+    pub(crate) fn get_page_type(&self) -> PageType {
+        match self.data[PAGETYPE_LOC] {
+            1 => PageType::Blob,
+            2 => PageType::Prefix,
+            _ => PageType::Heap,
+        }
+    }
+
+    pub(crate) fn update_page_type(&mut self, page_type: PageType) {
+        self.data[PAGETYPE_LOC] = page_type as u8;
+    }
+}
+
+/// Id of [`IdentityCompressor`], the default every page starts with. Its
+/// defining invariant is that it never touches the bytes `to_bytes` would
+/// have produced anyway, so a freshly created page (whose header byte at
+/// [`COMPRESSOR_ID_LOC`] is zero, like the rest of a zeroed page) round-trips
+/// through `to_bytes`/`from_bytes` exactly as it did before this feature
+/// existed.
+pub(crate) const IDENTITY_COMPRESSOR_ID: u8 = 0;
+/// Id of [`ZlibCompressor`].
+pub(crate) const ZLIB_COMPRESSOR_ID: u8 = 1;
+
+/// A codec that can shrink a page's packed-record region for storage and
+/// restore it exactly on read. Implementations are looked up by a one-byte
+/// id (see [`compressor_for_id`]) stashed in the page header at
+/// [`COMPRESSOR_ID_LOC`], so `Page::from_bytes` knows which one to use
+/// without being told out of band.
+pub(crate) trait Compressor {
+    fn compress(&self, bytes: &[u8]) -> Vec<u8>;
+    fn decompress(&self, bytes: &[u8]) -> Vec<u8>;
+}
+
+/// The default, no-op codec: `to_bytes` skips compression entirely for
+/// [`IDENTITY_COMPRESSOR_ID`] pages rather than calling into this, but it's
+/// still here to round out the registry for callers that look a codec up
+/// generically by id.
+pub(crate) struct IdentityCompressor;
+
+impl Compressor for IdentityCompressor {
+    fn compress(&self, bytes: &[u8]) -> Vec<u8> {
+        bytes.to_vec()
+    }
+
+    fn decompress(&self, bytes: &[u8]) -> Vec<u8> {
+        bytes.to_vec()
+    }
+}
+
+/// Zlib/deflate, via `flate2`. Deflate streams carry their own end-of-data
+/// marker, so `decompress` can be handed the packed-record region padded
+/// out with trailing zero bytes (as `Page::to_bytes` leaves it) and will
+/// stop reading once the real stream ends instead of choking on the
+/// padding.
+pub(crate) struct ZlibCompressor;
+
+impl Compressor for ZlibCompressor {
+    fn compress(&self, bytes: &[u8]) -> Vec<u8> {
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+        // A page's packed-record region is always well-formed bytes from an
+        // in-memory `Vec`-backed writer, so compression here can't fail.
+        encoder.write_all(bytes).expect("zlib compress");
+        encoder.finish().expect("zlib compress")
+    }
+
+    fn decompress(&self, bytes: &[u8]) -> Vec<u8> {
+        let mut decoder = ZlibDecoder::new(bytes);
+        let mut out = Vec::new();
+        // Only ever called on a region `to_bytes` produced with this same
+        // codec, so a failure here means the page is corrupt; `Page`'s
+        // caller is expected to have already verified its checksum (see
+        // `HeapFile`) before getting this far.
+        decoder.read_to_end(&mut out).expect("zlib decompress");
+        out
+    }
+}
+
+/// The compressor-id registry `to_bytes`/`from_bytes` dispatch through.
+/// Returns `None` for an id nothing is registered under, which callers
+/// treat as "leave the bytes alone" rather than panicking on an unknown or
+/// forward-incompatible tag.
+pub(crate) fn compressor_for_id(id: u8) -> Option<&'static dyn Compressor> {
+    static IDENTITY: IdentityCompressor = IdentityCompressor;
+    static ZLIB: ZlibCompressor = ZlibCompressor;
+    match id {
+        IDENTITY_COMPRESSOR_ID => Some(&IDENTITY),
+        ZLIB_COMPRESSOR_ID => Some(&ZLIB),
+        _ => None,
+    }
+}
+
+impl Page {
+    pub(crate) fn get_compressor_id(&self) -> u8 {
+        self.data[COMPRESSOR_ID_LOC]
+    }
+
+    /// Select the codec `to_bytes` should compress this page's
+    /// packed-record region with. Takes effect on the next `to_bytes` call;
+    /// has no effect on the page's in-memory layout.
+    pub fn set_compressor(&mut self, id: u8) {
+        self.data[COMPRESSOR_ID_LOC] = id;
+    }
+}
+
+/// Returns the `first_offset` header field out of a page's raw bytes,
+/// without requiring an owned `Page` -- in the same spirit as the zero-copy
+/// `raw_*` helpers further down, needed here because `Page::from_bytes`
+/// must read it before a `Page` exists to call `get_first_offset` on.
+pub(crate) fn raw_first_offset(data: &[u8]) -> Offset {
+    Offset::from_le_bytes(data[FIRSTOFFSET_LOC..TOTSLOTS_LOC].try_into().unwrap())
+}
+
+/// FNV-1a, used only to seed the Bloom filter's bit positions below -- not
+/// for anything where collision-resistance matters.
+fn fnv1a_64(bytes: &[u8]) -> u64 {
+    const OFFSET_BASIS: u64 = 0xcbf29ce484222325;
+    const PRIME: u64 = 0x100000001b3;
+    let mut hash = OFFSET_BASIS;
+    for &byte in bytes {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(PRIME);
+    }
+    hash
+}
+
+/// The `BLOOM_K` bit positions `bytes` maps to, derived from a single
+/// FNV-1a hash split into two 32-bit halves via double hashing
+/// (`h_i = h1 + i*h2 mod m`) instead of running `BLOOM_K` independent hash
+/// functions.
+fn bloom_bit_positions(bytes: &[u8]) -> impl Iterator<Item = usize> {
+    let hash = fnv1a_64(bytes);
+    let h1 = hash >> 32;
+    let h2 = hash & 0xFFFF_FFFF;
+    (0..BLOOM_K).map(move |i| (h1.wrapping_add(i.wrapping_mul(h2)) % BLOOM_BITS as u64) as usize)
+}
+
+/// An optional, page-embedded Bloom filter (see `BLOOM_LOC`) over every
+/// value currently added via `add_value`, letting a caller scanning a heap
+/// file skip a page outright when it definitely doesn't hold a given value
+/// instead of paying for a full slot-by-slot scan. `add_value` keeps it
+/// updated incrementally; a standard Bloom filter can't un-set bits on
+/// removal, so `delete_value` instead has `rebuild_filter` re-derive it
+/// from scratch off the slots still live afterwards.
+impl Page {
+    fn bloom_clear(&mut self) {
+        self.data[BLOOM_LOC..BLOOM_LOC + BLOOM_BYTES].fill(0);
+    }
+
+    fn bloom_set_bit(&mut self, bit: usize) {
+        self.data[BLOOM_LOC + bit / 8] |= 1 << (bit % 8);
+    }
+
+    fn bloom_get_bit(&self, bit: usize) -> bool {
+        self.data[BLOOM_LOC + bit / 8] & (1 << (bit % 8)) != 0
+    }
+
+    fn bloom_insert(&mut self, bytes: &[u8]) {
+        for bit in bloom_bit_positions(bytes) {
+            self.bloom_set_bit(bit);
+        }
+    }
+
+    /// Returns `false` only when `bytes` is definitely not among the values
+    /// added to this page -- a `true` result may still be a false positive.
+    pub fn may_contain(&self, bytes: &[u8]) -> bool {
+        bloom_bit_positions(bytes).all(|bit| self.bloom_get_bit(bit))
+    }
+
+    /// Re-derive the filter from every value the page's slots currently
+    /// hold, discarding whatever was set before. The only correct way to
+    /// handle a deletion, since a Bloom filter can't remove a single entry
+    /// in place. These internal re-reads are left out of `stats()`.
+    pub(crate) fn rebuild_filter(&mut self) {
+        use std::sync::atomic::Ordering::Relaxed;
+        let reads = self.counters.reads.load(Relaxed);
+        let bytes_read = self.counters.bytes_read.load(Relaxed);
+        self.bloom_clear();
+        for slot in 0..self.get_total_slot_headers() {
+            if let Some(bytes) = self.get_value(slot) {
+                self.bloom_insert(&bytes);
+            }
+        }
+        self.counters.reads.store(reads, Relaxed);
+        self.counters.bytes_read.store(bytes_read, Relaxed);
+    }
+}
 
 pub trait HeapPage {
     // Do not change these functions signatures (only the function bodies)
@@ -29,9 +289,21 @@ pub trait HeapPage {
 
     //Add function signatures for any helper function you need here
 
+    /// Verifies the structural invariants of the slotted page layout,
+    /// returning a descriptive `CrustyError` on the first one violated
+    /// instead of letting corruption surface later as a panic or silent
+    /// arithmetic underflow.
+    fn fsck(&self) -> Result<(), CrustyError>;
+
     // Deletion and addition utility functions:
-    fn compact(&mut self, del_offset: Offset, del_meta_loc: usize);
+    /// Full repacking pass: walks every live slot in descending offset order
+    /// and slides its bytes up against `PAGE_SIZE` with no gaps between them,
+    /// rebuilding `first_offset` and zeroing `fragmented_space`. Unlike the
+    /// old per-delete `compact`, this is not called on every `delete_value` --
+    /// see `add_value`'s contiguous-vs-fragmented check for when it runs.
+    fn compact(&mut self);
     fn get_next_slotid(&self) -> SlotId;
+    fn get_contiguous_free_space(&self) -> usize;
 
     // Header metadata utility functions:
     fn get_num_slots(&self) -> u16;
@@ -40,11 +312,17 @@ pub trait HeapPage {
     fn update_first_offset(&mut self, new_first_offset: Offset);
     fn get_total_slot_headers(&self) -> u16;
     fn update_total_slot_headers(&mut self, new_num: u16);
+    fn get_freelist_head(&self) -> SlotId;
+    fn update_freelist_head(&mut self, head: SlotId);
+    fn get_fragmented_space(&self) -> u16;
+    fn update_fragmented_space(&mut self, new_fragmented: u16);
 
     // Slot metadata utility functions:
     fn get_slot_meta_loc(&self, slot: SlotId) -> Option<usize>;
     fn get_slot_size(&self, slot: SlotId, slotloc: usize) -> u16;
     fn update_slot_size(&mut self, slot: SlotId, slotsize: u16, slotloc: usize);
+    fn is_slot_compressed(&self, slot: SlotId, slotloc: usize) -> bool;
+    fn update_slot_compressed(&mut self, slot: SlotId, compressed: bool, slotloc: usize);
     fn get_slot_offset(&self, slot: SlotId, slocloc: usize) -> Offset;
     fn update_slot_offset(&mut self, slot: SlotId, new_offset: Offset, slotloc: usize);
 
@@ -57,16 +335,50 @@ impl HeapPage for Page {
     /// Note that where the bytes are stored in the page does not matter (heap), but it
     /// should not change the slotId for any existing value. This means that
     /// bytes in the page may not follow the slot order.
-    /// If a slot is deleted you should reuse the slotId in the future.
-    /// The page should always assign the lowest available slot_id to an insertion.
+    /// If a slot is deleted its id is pushed onto an intrusive free list and is
+    /// reused LIFO (most-recently-freed first) by the next insertion, rather than
+    /// always reassigning the lowest available id -- see `get_next_slotid`.
     ///
     /// HINT: You can copy/clone bytes into a slice using the following function.
     /// They must have the same size.
     /// self.data[X..y].clone_from_slice(&bytes);
     fn add_value(&mut self, bytes: &[u8]) -> Option<SlotId> {
-        let entry_size = bytes.len() as Offset;
+        if self.get_page_type() == PageType::Prefix {
+            let result = self.add_value_prefix(bytes);
+            if result.is_some() {
+                use std::sync::atomic::Ordering::Relaxed;
+                self.counters.writes.fetch_add(1, Relaxed);
+                self.counters
+                    .bytes_written
+                    .fetch_add(bytes.len() as u64, Relaxed);
+            }
+            return result;
+        }
+        // Try LZ4 first (it prepends its own 4-byte original-length header),
+        // but only keep it if it actually shrinks the value -- otherwise
+        // store the bytes as-is and leave the slot's compressed bit clear.
+        // The free-space check below is against whichever form we keep,
+        // since that's what actually has to fit on the page.
+        let compressed = lz4_flex::compress_prepend_size(bytes);
+        let (stored_bytes, is_compressed) = if compressed.len() < bytes.len() {
+            (compressed.as_slice(), true)
+        } else {
+            (bytes, false)
+        };
+        let entry_size = stored_bytes.len() as Offset;
+        let needed = entry_size as usize + SLOT_META_SIZE;
+
+        // The happy path: there's enough contiguous room before first_offset
+        // already. Only when there isn't do we consider a compaction pass --
+        // and only run one if it would actually free up enough room, since a
+        // full repack is O(live slots) and shouldn't be paid on every insert.
+        if self.get_contiguous_free_space() < needed
+            && self.get_contiguous_free_space() + self.get_fragmented_space() as usize >= needed
+        {
+            self.compact();
+        }
 
-        if self.get_free_space() >= (entry_size as usize + SLOT_META_SIZE) {
+        if self.get_contiguous_free_space() >= needed {
             let slot_id: SlotId = self.get_next_slotid();
             let end_at = self.get_first_offset();
 
@@ -74,21 +386,39 @@ impl HeapPage for Page {
             self.update_num_slots(self.get_num_slots() + 1);
             self.update_first_offset(end_at - entry_size as Offset);
 
-            // Slot metadata updates
-            let slotmetaloc =
-                META_HEADER_SIZE + (SLOT_META_SIZE * self.get_total_slot_headers() as usize);
-
-            self.update_total_slot_headers(self.get_total_slot_headers() + 1);
-            let slot_endid: usize = slotmetaloc + SLOTID_SIZE;
-            let slotid_bytes = slot_id.to_le_bytes();
-            self.data[slotmetaloc..slot_endid].clone_from_slice(&slotid_bytes);
+            if self.get_freelist_head() != FREELIST_NIL {
+                // The slot's header already exists (at a fixed location keyed
+                // by its id, see `get_slot_meta_loc`); splice its stored "next"
+                // pointer into the freelist head instead of allocating anew.
+                let slotmetaloc = META_HEADER_SIZE + (SLOT_META_SIZE * slot_id as usize);
+                let next = self.get_slot_offset(slot_id, slotmetaloc);
+                self.update_freelist_head(next);
+            } else {
+                // Freelist was empty: this id has never had a header, so
+                // append a brand-new one at the end of the slot directory.
+                let slotmetaloc =
+                    META_HEADER_SIZE + (SLOT_META_SIZE * self.get_total_slot_headers() as usize);
+                self.update_total_slot_headers(self.get_total_slot_headers() + 1);
+                let slot_endid: usize = slotmetaloc + SLOTID_SIZE;
+                let slotid_bytes = slot_id.to_le_bytes();
+                self.data[slotmetaloc..slot_endid].clone_from_slice(&slotid_bytes);
+            }
 
             // Inserting data
             self.data[(end_at as usize - entry_size as usize)..(end_at as usize)]
-                .clone_from_slice(bytes);
+                .clone_from_slice(stored_bytes);
 
+            let slotmetaloc = META_HEADER_SIZE + (SLOT_META_SIZE * slot_id as usize);
             self.update_slot_offset(slot_id, end_at, slotmetaloc);
             self.update_slot_size(slot_id, entry_size, slotmetaloc);
+            self.update_slot_compressed(slot_id, is_compressed, slotmetaloc);
+            self.bloom_insert(bytes);
+
+            use std::sync::atomic::Ordering::Relaxed;
+            self.counters.writes.fetch_add(1, Relaxed);
+            self.counters
+                .bytes_written
+                .fetch_add(bytes.len() as u64, Relaxed);
 
             return Some(slot_id);
         }
@@ -97,72 +427,122 @@ impl HeapPage for Page {
 
     /// Return the bytes for the slotId. If the slotId is not valid then return None
     fn get_value(&self, slot_id: SlotId) -> Option<Vec<u8>> {
-        match self.get_slot_meta_loc(slot_id) {
-            Some(slotmetaloc) => {
-                let slot_size = self.get_slot_size(slot_id, slotmetaloc) as usize;
-                let offset = self.get_slot_offset(slot_id, slotmetaloc) as usize;
-                Some(self.data[(offset - slot_size)..offset].to_vec())
+        let result = if self.get_page_type() == PageType::Prefix {
+            self.get_value_prefix(slot_id)
+        } else {
+            match self.get_slot_meta_loc(slot_id) {
+                Some(slotmetaloc) => {
+                    let slot_size = self.get_slot_size(slot_id, slotmetaloc) as usize;
+                    let offset = self.get_slot_offset(slot_id, slotmetaloc) as usize;
+                    let stored = &self.data[(offset - slot_size)..offset];
+                    if self.is_slot_compressed(slot_id, slotmetaloc) {
+                        lz4_flex::decompress_size_prepended(stored).ok()
+                    } else {
+                        Some(stored.to_vec())
+                    }
+                }
+                None => None,
             }
-            None => None,
+        };
+        // Tracked here, once, so every path that reaches a value -- direct
+        // calls, `into_iter`, and `par_iter` alike -- is counted exactly
+        // once (see `PageCounters`).
+        use std::sync::atomic::Ordering::Relaxed;
+        self.counters.reads.fetch_add(1, Relaxed);
+        if let Some(bytes) = &result {
+            self.counters.bytes_read.fetch_add(bytes.len() as u64, Relaxed);
         }
+        result
     }
 
-    // Eager compaction function that is called at every deletion. Shifts the
-    // slots with offsets smaller than the deleted offset to make room for
-    // new data.
-    fn compact(&mut self, del_offset: Offset, del_meta_loc: usize) {
-        let mut cur_loc = del_meta_loc + SLOT_META_SIZE;
-        let total_slot_space =
-            META_HEADER_SIZE + (self.get_total_slot_headers() as usize * SLOT_META_SIZE);
-        let mut new_starting_idx = del_offset;
-        let mut new_offset = del_offset;
-
-        while cur_loc < total_slot_space {
-            let slot_id = SlotId::from_le_bytes(
-                self.data[cur_loc..cur_loc + SLOTID_SIZE]
-                    .try_into()
-                    .unwrap(),
-            );
-            let slots_offset = self.get_slot_offset(slot_id, cur_loc) as usize;
-            if slots_offset != 0 {
-                // if the slot id is valid
-                let slots_size = self.get_slot_size(slot_id, cur_loc) as usize;
-                // move the data
-
-                new_starting_idx = new_offset - slots_size as Offset;
-                self.data.copy_within(
-                    (slots_offset - slots_size)..slots_offset,
-                    new_starting_idx as usize,
-                );
-
-                // update the slot metadata
-                self.update_slot_offset(slot_id, new_offset, cur_loc);
+    // Full repacking pass (see the trait doc comment): collects every live
+    // slot, then replays them back-to-front against PAGE_SIZE in descending
+    // offset order so nothing overlaps and no gap is left behind. Resets
+    // fragmented_space to 0 since every hole it was tracking is gone.
+    fn compact(&mut self) {
+        use std::sync::atomic::Ordering::Relaxed;
+        self.counters.compactions.fetch_add(1, Relaxed);
+
+        let total_headers = self.get_total_slot_headers();
+        let mut live: Vec<(SlotId, usize, Offset, u16)> = Vec::new();
+        for slot in 0..total_headers {
+            let loc = META_HEADER_SIZE + (slot as usize * SLOT_META_SIZE);
+            let size = self.get_slot_size(slot, loc);
+            if size == 0 {
+                // Free (sitting on the intrusive free list), not in use.
+                continue;
+            }
+            let offset = self.get_slot_offset(slot, loc);
+            live.push((slot, loc, offset, size));
+        }
+        // Highest offset (closest to PAGE_SIZE, least likely to need moving)
+        // first, so each slot is replayed directly above the one before it.
+        live.sort_unstable_by(|a, b| b.2.cmp(&a.2));
+
+        let mut new_offset = PAGE_SIZE as Offset;
+        for (slot_id, loc, offset, size) in live {
+            let start = (offset - size) as usize;
+            let new_start = (new_offset - size) as usize;
+            if new_start != start {
+                self.data.copy_within(start..offset as usize, new_start);
+                self.counters.bytes_written.fetch_add(size as u64, Relaxed);
             }
-            cur_loc += SLOT_META_SIZE;
-            new_offset = new_starting_idx;
+            self.update_slot_offset(slot_id, new_offset, loc);
+            new_offset = new_start as Offset;
         }
+
+        self.update_first_offset(new_offset);
+        self.update_fragmented_space(0);
     }
 
     /// Delete the bytes/slot for the slotId. If the slotId is not valid then return None
-    /// The slotId for a deleted slot should be assigned to the next added value
+    /// The slotId for a deleted slot is pushed onto the intrusive free list (see
+    /// `get_next_slotid`) and is reused by a later added value.
     /// The space for the value should be free to use for a later added value.
     /// HINT: Return Some(()) for a valid delete
+    ///
+    /// This does *not* shift any other slot's data -- it just zeroes the
+    /// freed range and tracks its size in `fragmented_space`, so no other
+    /// slot's bytes move on delete (the Bloom filter is still rebuilt by
+    /// re-reading every live slot). The hole is only reclaimed later, by a
+    /// single `compact()` pass, when `add_value` actually needs the room.
     fn delete_value(&mut self, slot_id: SlotId) -> Option<()> {
+        if self.get_page_type() == PageType::Prefix {
+            // Append-only: removing an entry would invalidate every later
+            // entry's shared-prefix chain and the restart array's offsets.
+            return None;
+        }
         match self.get_slot_meta_loc(slot_id) {
             Some(slotmetaloc) => {
                 let slot_size = self.get_slot_size(slot_id, slotmetaloc) as usize;
                 let offset = self.get_slot_offset(slot_id, slotmetaloc) as usize;
                 self.data[(offset - slot_size)..offset].fill(0); // zeroing out the data
 
-                self.compact(offset as Offset, slotmetaloc);
-                // zeroing the values at the slot header
-                self.data[slotmetaloc..slotmetaloc + SLOTID_SIZE].fill(0);
+                // Push slot_id onto the free list: its header's now-unused
+                // offset field becomes the "next" link, and size 0 marks the
+                // header as free rather than live. Clear the compressed flag
+                // too, so the header's raw bytes are a literal 0 (the
+                // zero-copy `raw_slot_meta_loc` below checks for exactly
+                // that, without masking it off first).
+                let old_head = self.get_freelist_head();
                 self.update_slot_size(slot_id, 0, slotmetaloc);
-                self.update_slot_offset(slot_id, 0, slotmetaloc);
+                self.update_slot_compressed(slot_id, false, slotmetaloc);
+                self.update_slot_offset(slot_id, old_head, slotmetaloc);
+                self.update_freelist_head(slot_id);
 
                 self.update_num_slots(self.get_num_slots() - 1);
+                self.update_fragmented_space(self.get_fragmented_space() + slot_size as u16);
 
-                self.update_first_offset(self.get_first_offset() + slot_size as Offset);
+                // A Bloom filter can't un-set a single entry's bits, so the
+                // only correct way to drop `slot_id` out of it is to
+                // re-derive the whole thing from what's still live.
+                self.rebuild_filter();
+
+                use std::sync::atomic::Ordering::Relaxed;
+                self.counters.writes.fetch_add(1, Relaxed);
+                self.counters
+                    .bytes_written
+                    .fetch_add(slot_size as u64, Relaxed);
 
                 Some(())
             }
@@ -183,6 +563,14 @@ impl HeapPage for Page {
     /// Will be used by tests.
     #[allow(dead_code)]
     fn get_free_space(&self) -> usize {
+        self.get_contiguous_free_space() + self.get_fragmented_space() as usize
+    }
+
+    // The space immediately usable by a new value without compacting first,
+    // i.e. the gap between the slot header array and first_offset. Unlike
+    // `get_free_space`, this does not count fragmented holes left behind by
+    // deletes elsewhere in the data region.
+    fn get_contiguous_free_space(&self) -> usize {
         let first = self.get_first_offset() as usize;
         let headersize = self.get_header_size();
         if first < headersize {
@@ -191,6 +579,93 @@ impl HeapPage for Page {
         first - headersize
     }
 
+    fn fsck(&self) -> Result<(), CrustyError> {
+        if self.get_page_type() == PageType::Blob {
+            // A BLOB_PAGE has no slot directory to walk; it only needs its
+            // own chunk-length header checked.
+            return self.fsck_blob();
+        }
+        if self.get_page_type() == PageType::Prefix {
+            // A PREFIX_PAGE's entries and restart array aren't a slot
+            // directory either; check them on their own terms.
+            return self.fsck_prefix();
+        }
+
+        let header_size = self.get_header_size();
+        let first_offset = self.get_first_offset() as usize;
+
+        if first_offset < header_size || first_offset > PAGE_SIZE {
+            return Err(CrustyError::CrustyError(format!(
+                "first_offset {} is out of bounds [{}, {}]",
+                first_offset, header_size, PAGE_SIZE
+            )));
+        }
+
+        let total_headers = self.get_total_slot_headers();
+        let header_region_end = META_HEADER_SIZE + (total_headers as usize * SLOT_META_SIZE);
+        if header_region_end > first_offset {
+            return Err(CrustyError::CrustyError(format!(
+                "slot header region ends at {} but overlaps the data region starting at {}",
+                header_region_end, first_offset
+            )));
+        }
+
+        // Live slots' byte ranges, used to check for overlaps below.
+        let mut live_ranges: Vec<(usize, usize)> = Vec::new();
+        let mut live_bytes = 0usize;
+
+        for slot in 0..total_headers {
+            let loc = META_HEADER_SIZE + (slot as usize * SLOT_META_SIZE);
+            let size = self.get_slot_size(slot, loc) as usize;
+            if size == 0 {
+                // Free (sitting on the intrusive free list), not in use.
+                continue;
+            }
+            let offset = self.get_slot_offset(slot, loc) as usize;
+            let start = offset.checked_sub(size).ok_or_else(|| {
+                CrustyError::CrustyError(format!(
+                    "slot {} has offset {} smaller than its size {}",
+                    slot, offset, size
+                ))
+            })?;
+            if start < first_offset || offset > PAGE_SIZE {
+                return Err(CrustyError::CrustyError(format!(
+                    "slot {} byte range [{}, {}) falls outside the data region [{}, {})",
+                    slot, start, offset, first_offset, PAGE_SIZE
+                )));
+            }
+            for &(other_start, other_end) in &live_ranges {
+                if start < other_end && other_start < offset {
+                    return Err(CrustyError::CrustyError(format!(
+                        "slot {} byte range [{}, {}) overlaps another slot's range [{}, {})",
+                        slot, start, offset, other_start, other_end
+                    )));
+                }
+            }
+            live_ranges.push((start, offset));
+            live_bytes += size;
+        }
+
+        let live_count = live_ranges.len() as u16;
+        if live_count != self.get_num_slots() {
+            return Err(CrustyError::CrustyError(format!(
+                "num_slots is {} but {} slot headers are actually in use",
+                self.get_num_slots(),
+                live_count
+            )));
+        }
+
+        let free_space = self.get_free_space();
+        if live_bytes + free_space + header_size != PAGE_SIZE {
+            return Err(CrustyError::CrustyError(format!(
+                "live bytes ({}) + free space ({}) + header size ({}) != PAGE_SIZE ({})",
+                live_bytes, free_space, header_size, PAGE_SIZE
+            )));
+        }
+
+        Ok(())
+    }
+
     // A utility function that returns the number of slots in use on the page.
     fn get_num_slots(&self) -> u16 {
         u16::from_le_bytes(
@@ -227,6 +702,11 @@ impl HeapPage for Page {
     // of whether they are currently in use (helpful for determining where the
     // boundary is between header end and free space).
     fn get_total_slot_headers(&self) -> u16 {
+        if self.get_page_type() == PageType::Prefix {
+            // Prefix-mode pages have no slot directory; the iterator just
+            // needs an entry count to bound `next_slot` against.
+            return self.prefix_num_entries();
+        }
         u16::from_le_bytes(self.data[TOTSLOTS_LOC..SLOTSTART_LOC].try_into().unwrap())
     }
 
@@ -237,95 +717,121 @@ impl HeapPage for Page {
         self.data[TOTSLOTS_LOC..SLOTSTART_LOC].copy_from_slice(&new_num_bytes);
     }
 
-    // Returns the smallest available SlotId.
-    fn get_next_slotid(&self) -> SlotId {
-        let num_slotids = self.get_num_slots();
-        let total_slot_headers = self.get_total_slot_headers();
-
-        if total_slot_headers == num_slotids {
-            // if all the slotids from 0 to num are in use
-            return num_slotids as SlotId;
-        }
-
-        let mut cur_loc = META_HEADER_SIZE;
-        let total_space = cur_loc + (total_slot_headers as usize * SLOT_META_SIZE);
+    // A utility function that returns the SlotId at the head of the
+    // intrusive free list, or `FREELIST_NIL` if no slots are free.
+    fn get_freelist_head(&self) -> SlotId {
+        SlotId::from_le_bytes(
+            self.data[FREELIST_HEAD_LOC..PAGETYPE_LOC]
+                .try_into()
+                .unwrap(),
+        )
+    }
 
-        let mut slot_vec = Vec::new();
+    // A utility function to update the head of the intrusive free list.
+    fn update_freelist_head(&mut self, head: SlotId) {
+        let head_bytes = head.to_le_bytes();
+        self.data[FREELIST_HEAD_LOC..PAGETYPE_LOC].copy_from_slice(&head_bytes);
+    }
 
-        while cur_loc < total_space {
-            let cur_slotid = SlotId::from_le_bytes(
-                self.data[cur_loc..cur_loc + SLOTID_SIZE]
-                    .try_into()
-                    .unwrap(),
-            );
-            if cur_slotid == 0 && self.get_slot_offset(cur_slotid, cur_loc) == 0 {
-                cur_loc += SLOT_META_SIZE;
-                continue;
-            }
-            slot_vec.push(cur_slotid);
-            cur_loc += SLOT_META_SIZE;
-        }
-        slot_vec.sort();
+    // A utility function that returns the number of bytes sitting in
+    // deleted-but-not-yet-compacted slots within the data region (see
+    // `delete_value`/`compact`).
+    fn get_fragmented_space(&self) -> u16 {
+        u16::from_le_bytes(
+            self.data[FRAGMENTED_LOC..SLOTSTART_LOC]
+                .try_into()
+                .unwrap(),
+        )
+    }
 
-        let mut min_missing = num_slotids;
-        let mut exp_id = 0;
+    // A utility function to update the fragmented-space counter.
+    fn update_fragmented_space(&mut self, new_fragmented: u16) {
+        self.data[FRAGMENTED_LOC..SLOTSTART_LOC].copy_from_slice(&new_fragmented.to_le_bytes());
+    }
 
-        for id in slot_vec {
-            if id != exp_id {
-                min_missing = exp_id;
-                break;
-            }
-            exp_id = id + 1;
+    // Returns the SlotId that the next `add_value` will use: the head of the
+    // intrusive free list if one is free (LIFO reuse of the most recently
+    // deleted slot), or else a brand new id one past the last ever allocated.
+    // This is O(1); it no longer scans/sorts every slot header and does not
+    // guarantee the lowest available id.
+    fn get_next_slotid(&self) -> SlotId {
+        let head = self.get_freelist_head();
+        if head != FREELIST_NIL {
+            head
+        } else {
+            self.get_total_slot_headers()
         }
-        min_missing as SlotId
     }
 
     // A utility function that returns the location of the metadata for
-    // the given slot.
+    // the given slot. A slot's header lives at a fixed location keyed by its
+    // id (new ids are always handed out as `get_total_slot_headers()`, i.e.
+    // the next unused header position, so the id *is* the header's index),
+    // so this is a direct O(1) lookup rather than a scan.
     fn get_slot_meta_loc(&self, slot: SlotId) -> Option<usize> {
-        let mut cur_loc = META_HEADER_SIZE;
-        let total_space = cur_loc + (self.get_total_slot_headers() as usize * SLOT_META_SIZE);
-
-        while cur_loc < total_space {
-            let cur_slotid = SlotId::from_le_bytes(
-                self.data[cur_loc..cur_loc + SLOTID_SIZE]
-                    .try_into()
-                    .unwrap(),
-            );
-            if cur_slotid == slot {
-                if cur_slotid == 0 && self.get_slot_offset(cur_slotid, cur_loc) == 0 {
-                    cur_loc += SLOT_META_SIZE;
-                    continue;
-                }
-
-                return Some(cur_loc);
-            }
-            cur_loc += SLOT_META_SIZE;
+        if slot >= self.get_total_slot_headers() {
+            return None;
         }
-        None
+        let loc = META_HEADER_SIZE + (slot as usize * SLOT_META_SIZE);
+        if self.get_slot_size(slot, loc) == 0 {
+            // Free (sitting on the intrusive free list), not a live value.
+            return None;
+        }
+        Some(loc)
     }
 
-    // Returns the size of the given slot.
+    // Returns the size of the given slot (masking off the top two bits, which
+    // are the "compressed" and large-value flags rather than part of the
+    // byte count).
     fn get_slot_size(&self, _slot: SlotId, loc: usize) -> u16 {
         let loc_slot_size = loc + SLOTID_SIZE;
         let loc_endof_slot_size = loc_slot_size + OFFSET_SIZE;
 
-        u16::from_le_bytes(
+        let raw = u16::from_le_bytes(
             self.data[loc_slot_size..loc_endof_slot_size]
                 .try_into()
                 .unwrap(),
-        )
+        );
+        raw & SLOT_SIZE_MASK
     }
 
-    // Updates the size of the given slot.
+    // Updates the size of the given slot, preserving whatever compressed
+    // flag is already stored there (use `update_slot_compressed` to change
+    // that bit).
     fn update_slot_size(&mut self, _slot: SlotId, slotsize: u16, loc: usize) {
         let loc_slot_size = loc + SLOTID_SIZE;
         let loc_endof_slot_size = loc_slot_size + OFFSET_SIZE;
 
-        let size_bytes = slotsize.to_le_bytes();
+        let existing_flag = u16::from_le_bytes(
+            self.data[loc_slot_size..loc_endof_slot_size]
+                .try_into()
+                .unwrap(),
+        ) & SLOT_COMPRESSED_FLAG;
+        let size_bytes = ((slotsize & SLOT_SIZE_MASK) | existing_flag).to_le_bytes();
         self.data[loc_slot_size..loc_endof_slot_size].copy_from_slice(&size_bytes);
     }
 
+    // Returns whether the given slot's stored bytes are LZ4-compressed.
+    fn is_slot_compressed(&self, _slot: SlotId, loc: usize) -> bool {
+        let loc_slot_size = loc + SLOTID_SIZE;
+        let loc_endof_slot_size = loc_slot_size + OFFSET_SIZE;
+        let raw = u16::from_le_bytes(
+            self.data[loc_slot_size..loc_endof_slot_size]
+                .try_into()
+                .unwrap(),
+        );
+        raw & SLOT_COMPRESSED_FLAG != 0
+    }
+
+    // Sets or clears the given slot's compressed flag, preserving its size.
+    fn update_slot_compressed(&mut self, _slot: SlotId, compressed: bool, loc: usize) {
+        let size = self.get_slot_size(_slot, loc);
+        let flag = if compressed { SLOT_COMPRESSED_FLAG } else { 0 };
+        let loc_slot_size = loc + SLOTID_SIZE;
+        let loc_endof_slot_size = loc_slot_size + OFFSET_SIZE;
+        self.data[loc_slot_size..loc_endof_slot_size].copy_from_slice(&(size | flag).to_le_bytes());
+    }
+
     // Returns the offset of the given slot (where its offset is the location
     // of the slot's last byte).
     fn get_slot_offset(&self, _slot: SlotId, loc: usize) -> Offset {
@@ -361,6 +867,16 @@ pub struct HeapPageIntoIter {
     next_slot: SlotId,
 }
 
+impl HeapPageIntoIter {
+    /// Whether `slot` (as just yielded by `next()`) holds a `BlobRedirect`
+    /// rather than inline bytes -- mirrors `Page::is_large_value`, exposed
+    /// so a caller with cross-page access this iterator lacks (e.g.
+    /// `BackendPageIterator`) can reassemble the chain itself.
+    pub(crate) fn is_large_value(&self, slot: SlotId) -> bool {
+        self.page.is_large_value(slot)
+    }
+}
+
 /// The implementation of the (consuming) page iterator.
 /// This should return the values in slotId order (ascending)
 impl Iterator for HeapPageIntoIter {
@@ -368,6 +884,10 @@ impl Iterator for HeapPageIntoIter {
     type Item = (Vec<u8>, SlotId);
 
     fn next(&mut self) -> Option<Self::Item> {
+        if self.page.get_page_type() == PageType::Blob {
+            // BLOB_PAGEs don't hold a slot directory -- nothing to iterate.
+            return None;
+        }
         if self.next_slot >= self.page.get_total_slot_headers() {
             return None;
         }
@@ -402,99 +922,1181 @@ impl IntoIterator for Page {
     }
 }
 
-#[cfg(test)]
-mod tests {
-    use std::collections::VecDeque;
+/// A read-only, rayon `IndexedParallelIterator` counterpart to `into_iter`:
+/// same `(Vec<u8>, SlotId)` pairs, same slot-id order, same skip of deleted
+/// slots, but split across threads instead of walked one at a time. Slots
+/// are independent once the slot directory is parsed, so the only
+/// sequential step is collecting which ids are live; from there rayon's
+/// standard indexed-`Producer` recipe recursively splits that list of ids in
+/// half, and each half is read concurrently through a shared `&Page` (a
+/// page's layout is never mutated by a read, so this is safely `Send + Sync`
+/// with no `unsafe` needed).
+pub struct PagePar<'a> {
+    page: &'a Page,
+    live_slots: Vec<SlotId>,
+}
 
-    use super::*;
-    use common::testutil::init;
-    use common::testutil::*;
-    use common::Tuple;
-    use rand::Rng;
+impl Page {
+    /// Parallel counterpart to `into_iter` (see `PagePar`). Borrows `self`
+    /// rather than consuming it, since nothing about a parallel scan needs
+    /// to take ownership of the page.
+    ///
+    /// Only supports a `PageType::Heap` page: `get_slot_meta_loc`'s notion
+    /// of a "live slot" is specific to the heap-mode slot directory, and
+    /// doesn't carry over to a `PageType::Prefix` page (whose entries are
+    /// only decodable in order from a restart point, not addressable by an
+    /// independent slot id) or a `PageType::Blob` page (which has no slot
+    /// directory at all -- see `HeapPageIntoIter::next`). Returns an error
+    /// for either rather than silently returning a wrong or empty set of
+    /// values.
+    pub fn par_iter(&self) -> Result<PagePar<'_>, CrustyError> {
+        if self.get_page_type() != PageType::Heap {
+            return Err(CrustyError::CrustyError(format!(
+                "par_iter only supports PageType::Heap pages, got {:?}",
+                self.get_page_type()
+            )));
+        }
+        let total = self.get_total_slot_headers();
+        let live_slots = (0..total)
+            .filter(|&slot| self.get_slot_meta_loc(slot).is_some())
+            .collect();
+        Ok(PagePar {
+            page: self,
+            live_slots,
+        })
+    }
+}
 
-    /// Limits how on how many bytes we can use for page metadata / header
-    pub const FIXED_HEADER_SIZE: usize = 8;
-    pub const HEADER_PER_VAL_SIZE: usize = 6;
+impl<'a> rayon::iter::ParallelIterator for PagePar<'a> {
+    type Item = (Vec<u8>, SlotId);
 
-    #[test]
-    fn hs_page_sizes_header_free_space() {
-        init();
-        let p = Page::new(0);
-        assert_eq!(0, p.get_page_id());
+    fn drive_unindexed<C>(self, consumer: C) -> C::Result
+    where
+        C: rayon::iter::plumbing::UnindexedConsumer<Self::Item>,
+    {
+        rayon::iter::plumbing::bridge(self, consumer)
+    }
 
-        assert_eq!(PAGE_SIZE - p.get_header_size(), p.get_free_space());
+    fn opt_len(&self) -> Option<usize> {
+        Some(self.live_slots.len())
     }
+}
 
-    #[test]
-    fn hs_page_debug_insert() {
-        init();
-        let mut p = Page::new(0);
-        let n = 20;
-        let size = 20;
-        let vals = get_ascending_vec_of_byte_vec_02x(n, size, size);
-        for x in &vals {
-            p.add_value(x);
-        }
-        assert_eq!(
-            p.get_free_space(),
-            PAGE_SIZE - p.get_header_size() - n * size
-        );
+impl<'a> rayon::iter::IndexedParallelIterator for PagePar<'a> {
+    fn len(&self) -> usize {
+        self.live_slots.len()
     }
 
-    #[test]
-    fn hs_page_simple_insert() {
-        init();
-        let mut p = Page::new(5);
-        let tuple = int_vec_to_tuple(vec![0, 1, 2]);
-        let tuple_bytes = serde_cbor::to_vec(&tuple).unwrap();
-        let byte_len = tuple_bytes.len();
-        assert_eq!(Some(0), p.add_value(&tuple_bytes));
-        assert_eq!(
-            PAGE_SIZE - byte_len - p.get_header_size(),
-            p.get_free_space()
-        );
-        let tuple_bytes2 = serde_cbor::to_vec(&tuple).unwrap();
-        assert_eq!(Some(1), p.add_value(&tuple_bytes2));
-        assert_eq!(
-            PAGE_SIZE - p.get_header_size() - byte_len - byte_len,
-            p.get_free_space()
-        );
+    fn drive<C>(self, consumer: C) -> C::Result
+    where
+        C: rayon::iter::plumbing::Consumer<Self::Item>,
+    {
+        rayon::iter::plumbing::bridge(self, consumer)
     }
 
-    #[test]
-    fn hs_page_space() {
-        init();
-        let mut p = Page::new(0);
-        let size = 10;
-        let bytes = get_random_byte_vec(size);
-        assert_eq!(10, bytes.len());
-        assert_eq!(Some(0), p.add_value(&bytes));
-        assert_eq!(PAGE_SIZE - p.get_header_size() - size, p.get_free_space());
-        assert_eq!(Some(1), p.add_value(&bytes));
-        assert_eq!(
-            PAGE_SIZE - p.get_header_size() - size * 2,
-            p.get_free_space()
-        );
-        assert_eq!(Some(2), p.add_value(&bytes));
-        assert_eq!(
-            PAGE_SIZE - p.get_header_size() - size * 3,
-            p.get_free_space()
-        );
+    fn with_producer<CB>(self, callback: CB) -> CB::Output
+    where
+        CB: rayon::iter::plumbing::ProducerCallback<Self::Item>,
+    {
+        callback.callback(PageSlotProducer {
+            page: self.page,
+            live_slots: &self.live_slots,
+        })
     }
+}
 
-    #[test]
-    fn hs_page_get_value() {
-        init();
-        let mut p = Page::new(0);
+/// The `Producer` half of `PagePar`'s indexed-iterator recipe: a range of
+/// live slot ids (a slice, not a copy of the page) plus a shared view of the
+/// page to read them out of. `split_at` just splits the slice -- the page
+/// itself is never divided, only borrowed again for the other half.
+struct PageSlotProducer<'a> {
+    page: &'a Page,
+    live_slots: &'a [SlotId],
+}
 
-        let tuple = int_vec_to_tuple(vec![0, 1, 2]);
-        let tuple_bytes = serde_cbor::to_vec(&tuple).unwrap();
-        assert_eq!(Some(0), p.add_value(&tuple_bytes));
+impl<'a> rayon::iter::plumbing::Producer for PageSlotProducer<'a> {
+    type Item = (Vec<u8>, SlotId);
+    type IntoIter = PageSlotIter<'a>;
 
-        let check_bytes = p.get_value(0).unwrap();
-        let check_tuple: Tuple = serde_cbor::from_slice(&check_bytes).unwrap();
-        assert_eq!(tuple_bytes, check_bytes);
-        assert_eq!(tuple, check_tuple);
+    fn into_iter(self) -> Self::IntoIter {
+        PageSlotIter {
+            page: self.page,
+            live_slots: self.live_slots,
+            next: 0,
+            end: self.live_slots.len(),
+        }
+    }
+
+    fn split_at(self, index: usize) -> (Self, Self) {
+        let (left, right) = self.live_slots.split_at(index);
+        (
+            PageSlotProducer {
+                page: self.page,
+                live_slots: left,
+            },
+            PageSlotProducer {
+                page: self.page,
+                live_slots: right,
+            },
+        )
+    }
+}
+
+/// Sequential iterator over one `PageSlotProducer`'s share of the live slot
+/// ids. Every id in `live_slots` was confirmed live when `par_iter()` built
+/// it, and the page can't have been mutated since (this holds `&Page`), so
+/// `get_value` reading back `None` here would mean that invariant broke.
+struct PageSlotIter<'a> {
+    page: &'a Page,
+    live_slots: &'a [SlotId],
+    next: usize,
+    end: usize,
+}
+
+impl<'a> PageSlotIter<'a> {
+    fn read(&self, at: usize) -> (Vec<u8>, SlotId) {
+        let slot = self.live_slots[at];
+        let bytes = self
+            .page
+            .get_value(slot)
+            .expect("slot was live when par_iter() collected it");
+        (bytes, slot)
+    }
+}
+
+impl<'a> Iterator for PageSlotIter<'a> {
+    type Item = (Vec<u8>, SlotId);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.next >= self.end {
+            return None;
+        }
+        let item = self.read(self.next);
+        self.next += 1;
+        Some(item)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = self.end - self.next;
+        (remaining, Some(remaining))
+    }
+}
+
+impl<'a> DoubleEndedIterator for PageSlotIter<'a> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        if self.next >= self.end {
+            return None;
+        }
+        self.end -= 1;
+        Some(self.read(self.end))
+    }
+}
+
+impl<'a> ExactSizeIterator for PageSlotIter<'a> {}
+
+/// A value stored with MVCC visibility metadata: `xmin` is the transaction
+/// that created it, `xmax` is the transaction that deleted it (if any, once
+/// committed-visible to a reader's snapshot the tuple is gone for them).
+/// This is stored transparently as the payload of a normal slot (through
+/// `add_value`/`get_value`), so the slot-directory layout -- and every
+/// existing test that assumes a fixed 6-byte slot header -- is untouched;
+/// only callers that opt into the `_mvcc` methods pay for it.
+#[derive(Serialize, Deserialize, Clone)]
+struct VersionedRecord {
+    xmin: TransactionId,
+    xmax: Option<TransactionId>,
+    bytes: Vec<u8>,
+}
+
+/// A read snapshot derived from a scanning transaction's id plus the set of
+/// transactions known to have committed. A tuple is visible if its creator
+/// is committed-before-snapshot (or is the snapshot's own transaction) and,
+/// if it has been deleted, its deleter is not yet committed-visible.
+pub struct Snapshot<'a> {
+    pub tid: TransactionId,
+    pub committed: &'a std::collections::HashSet<TransactionId>,
+}
+
+impl<'a> Snapshot<'a> {
+    pub fn new(tid: TransactionId, committed: &'a std::collections::HashSet<TransactionId>) -> Self {
+        Self { tid, committed }
+    }
+
+    fn committed_before_snapshot(&self, txn: &TransactionId) -> bool {
+        *txn == self.tid || self.committed.contains(txn)
+    }
+
+    fn is_visible(&self, record: &VersionedRecord) -> bool {
+        if !self.committed_before_snapshot(&record.xmin) {
+            return false;
+        }
+        match &record.xmax {
+            Some(deleter) => !self.committed_before_snapshot(deleter),
+            None => true,
+        }
+    }
+}
+
+impl Page {
+    /// Insert `bytes` as a new MVCC-versioned tuple created by `xmin`.
+    pub fn add_value_mvcc(&mut self, bytes: &[u8], xmin: TransactionId) -> Option<SlotId> {
+        let record = VersionedRecord {
+            xmin,
+            xmax: None,
+            bytes: bytes.to_vec(),
+        };
+        let encoded = serde_cbor::to_vec(&record).ok()?;
+        self.add_value(&encoded)
+    }
+
+    fn get_versioned_record(&self, slot_id: SlotId) -> Option<VersionedRecord> {
+        let bytes = self.get_value(slot_id)?;
+        serde_cbor::from_slice(&bytes).ok()
+    }
+
+    /// Read `slot_id`'s bytes if they are visible under `snapshot`, skipping
+    /// versions that the snapshot's transaction should not see.
+    pub fn get_value_mvcc(&self, slot_id: SlotId, snapshot: &Snapshot) -> Option<Vec<u8>> {
+        let record = self.get_versioned_record(slot_id)?;
+        if snapshot.is_visible(&record) {
+            Some(record.bytes)
+        } else {
+            None
+        }
+    }
+
+    /// Stamp `slot_id` as deleted by `xmax`. Because the serialized record
+    /// grows when `xmax` moves from `None` to `Some`, this re-inserts the
+    /// stamped record (the slot directory entry may move); returns the slot
+    /// id the stamped record now lives at.
+    pub fn delete_value_mvcc(&mut self, slot_id: SlotId, xmax: TransactionId) -> Option<SlotId> {
+        let mut record = self.get_versioned_record(slot_id)?;
+        record.xmax = Some(xmax);
+        self.delete_value(slot_id)?;
+        let encoded = serde_cbor::to_vec(&record).ok()?;
+        self.add_value(&encoded)
+    }
+
+    /// Remove `slot_id` outright, as a savepoint rollback undoing one of this
+    /// transaction's own inserts does; no stamp is reset first.
+    pub fn undo_insert_mvcc(&mut self, slot_id: SlotId) -> Option<()> {
+        self.delete_value(slot_id)
+    }
+
+    /// Reset `slot_id`'s `xmax` stamp back to live, as a savepoint rollback
+    /// undoing one of this transaction's own deletes would do.
+    pub fn undo_delete_mvcc(&mut self, slot_id: SlotId) -> Option<SlotId> {
+        let mut record = self.get_versioned_record(slot_id)?;
+        record.xmax = None;
+        self.delete_value(slot_id)?;
+        let encoded = serde_cbor::to_vec(&record).ok()?;
+        self.add_value(&encoded)
+    }
+}
+
+/// A handle returned by `Page::vacant_slot`, naming the `SlotId` a reserved
+/// slot will land at before its bytes exist. Modeled on `slab::VacantEntry`:
+/// reserving space and a slot id up front lets a caller build a record that
+/// embeds its own id (a self-referential tuple, an index entry pointing back
+/// at itself) before it commits the value with `fill`.
+///
+/// Dropping this without calling `fill` releases the reservation -- the slot
+/// id goes back on the same intrusive free list `delete_value` uses, and the
+/// reserved space is returned to `first_offset` -- so an abandoned
+/// reservation leaves no fragmented space behind. A slot header newly
+/// allocated for it stays allocated, on the free list, for the next insert
+/// to reuse.
+pub struct VacantSlot<'a> {
+    page: &'a mut Page,
+    slot_id: SlotId,
+    reserved_at: Offset,
+    max_size: usize,
+    filled: bool,
+}
+
+impl<'a> VacantSlot<'a> {
+    /// The id this slot will occupy once filled.
+    pub fn slot_id(&self) -> SlotId {
+        self.slot_id
+    }
+
+    /// Commit `bytes` to this reservation, returning the slot id it landed
+    /// at. `bytes` must fit within the size bound passed to `vacant_slot` --
+    /// that's what guarantees this can't fail the way `add_value` can.
+    pub fn fill(mut self, bytes: &[u8]) -> SlotId {
+        let slot_id = self.slot_id;
+        self.page
+            .fill_vacant(slot_id, self.reserved_at, self.max_size, bytes);
+        self.filled = true;
+        slot_id
+    }
+}
+
+impl<'a> Drop for VacantSlot<'a> {
+    fn drop(&mut self) {
+        if !self.filled {
+            self.page.release_vacant(self.slot_id, self.reserved_at);
+        }
+    }
+}
+
+impl Page {
+    /// Reserve a slot id and up to `max_size` bytes of space for a value
+    /// that isn't built yet, without writing anything. Returns `None` on
+    /// the same terms `add_value` would for a value of that size (including
+    /// running a `compact()` first if that would be enough to fit it).
+    pub fn vacant_slot(&mut self, max_size: usize) -> Option<VacantSlot<'_>> {
+        let needed = max_size + SLOT_META_SIZE;
+        if self.get_contiguous_free_space() < needed
+            && self.get_contiguous_free_space() + self.get_fragmented_space() as usize >= needed
+        {
+            self.compact();
+        }
+        if self.get_contiguous_free_space() < needed {
+            return None;
+        }
+
+        let slot_id = self.get_next_slotid();
+        let reserved_at = self.get_first_offset();
+
+        self.update_num_slots(self.get_num_slots() + 1);
+        self.update_first_offset(reserved_at - max_size as Offset);
+
+        if self.get_freelist_head() != FREELIST_NIL {
+            let slotmetaloc = META_HEADER_SIZE + (SLOT_META_SIZE * slot_id as usize);
+            let next = self.get_slot_offset(slot_id, slotmetaloc);
+            self.update_freelist_head(next);
+        } else {
+            let slotmetaloc =
+                META_HEADER_SIZE + (SLOT_META_SIZE * self.get_total_slot_headers() as usize);
+            self.update_total_slot_headers(self.get_total_slot_headers() + 1);
+            let slot_endid = slotmetaloc + SLOTID_SIZE;
+            self.data[slotmetaloc..slot_endid].clone_from_slice(&slot_id.to_le_bytes());
+        }
+
+        Some(VacantSlot {
+            page: self,
+            slot_id,
+            reserved_at,
+            max_size,
+            filled: false,
+        })
+    }
+
+    /// Write `bytes` (compressed the same way `add_value` would) into a
+    /// reservation made by `vacant_slot`, finishing the slot's header.
+    fn fill_vacant(&mut self, slot_id: SlotId, reserved_at: Offset, max_size: usize, bytes: &[u8]) {
+        let compressed = lz4_flex::compress_prepend_size(bytes);
+        let (stored_bytes, is_compressed) = if compressed.len() < bytes.len() {
+            (compressed.as_slice(), true)
+        } else {
+            (bytes, false)
+        };
+        let entry_size = stored_bytes.len();
+
+        let start = reserved_at as usize - entry_size;
+        self.data[start..reserved_at as usize].clone_from_slice(stored_bytes);
+
+        // Anything left over between the reservation and what actually got
+        // used is a hole exactly like the ones `delete_value` leaves behind
+        // -- tracked the same way so a later `compact()` reclaims it.
+        let slack = max_size - entry_size;
+        if slack > 0 {
+            self.update_fragmented_space(self.get_fragmented_space() + slack as u16);
+        }
+
+        let slotmetaloc = META_HEADER_SIZE + (SLOT_META_SIZE * slot_id as usize);
+        self.update_slot_offset(slot_id, reserved_at, slotmetaloc);
+        self.update_slot_size(slot_id, entry_size as u16, slotmetaloc);
+        self.update_slot_compressed(slot_id, is_compressed, slotmetaloc);
+        self.bloom_insert(bytes);
+    }
+
+    /// Undo an abandoned reservation: restore `first_offset`, so the reserved
+    /// bytes become contiguous free space again rather than fragmented space,
+    /// and push `slot_id` onto the intrusive free list the same way
+    /// `delete_value` does.
+    fn release_vacant(&mut self, slot_id: SlotId, reserved_at: Offset) {
+        self.update_first_offset(reserved_at);
+
+        let slotmetaloc = META_HEADER_SIZE + (SLOT_META_SIZE * slot_id as usize);
+        let old_head = self.get_freelist_head();
+        self.update_slot_size(slot_id, 0, slotmetaloc);
+        self.update_slot_compressed(slot_id, false, slotmetaloc);
+        self.update_slot_offset(slot_id, old_head, slotmetaloc);
+        self.update_freelist_head(slot_id);
+
+        self.update_num_slots(self.get_num_slots() - 1);
+    }
+}
+
+/// Internal, runtime-only read/write/compaction counters for a `Page` --
+/// never part of `to_bytes`/`from_bytes`, so a page read back off disk
+/// always starts fresh. `get_value` is reachable through a shared `&Page`
+/// (see `par_iter`), so every counter is an atomic rather than a plain
+/// integer; relaxed ordering is enough since these only inform callers,
+/// never gate access to the page's bytes. See `PageStats` for the
+/// snapshot callers actually see.
+#[derive(Debug, Default)]
+pub(crate) struct PageCounters {
+    reads: std::sync::atomic::AtomicU64,
+    writes: std::sync::atomic::AtomicU64,
+    bytes_read: std::sync::atomic::AtomicU64,
+    bytes_written: std::sync::atomic::AtomicU64,
+    compactions: std::sync::atomic::AtomicU64,
+}
+
+impl Clone for PageCounters {
+    fn clone(&self) -> Self {
+        use std::sync::atomic::Ordering::Relaxed;
+        PageCounters {
+            reads: std::sync::atomic::AtomicU64::new(self.reads.load(Relaxed)),
+            writes: std::sync::atomic::AtomicU64::new(self.writes.load(Relaxed)),
+            bytes_read: std::sync::atomic::AtomicU64::new(self.bytes_read.load(Relaxed)),
+            bytes_written: std::sync::atomic::AtomicU64::new(self.bytes_written.load(Relaxed)),
+            compactions: std::sync::atomic::AtomicU64::new(self.compactions.load(Relaxed)),
+        }
+    }
+}
+
+/// A point-in-time snapshot of a page's `PageCounters`, returned by
+/// `Page::stats`. Lets a caller (typically a test) assert exactly how many
+/// reads, writes, and byte-copies a workload triggered -- e.g. that a
+/// `delete_value` followed by a smaller `add_value` reuses the fragmented
+/// hole instead of paying for a full `compact()`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PageStats {
+    pub reads: u64,
+    pub writes: u64,
+    pub bytes_read: u64,
+    pub bytes_written: u64,
+    pub compactions: u64,
+}
+
+impl Page {
+    /// Snapshot this page's read/write/compaction counters since it was
+    /// created (see `PageStats`); these are in-memory bookkeeping only and
+    /// do not survive a `to_bytes`/`from_bytes` round trip.
+    pub fn stats(&self) -> PageStats {
+        use std::sync::atomic::Ordering::Relaxed;
+        PageStats {
+            reads: self.counters.reads.load(Relaxed),
+            writes: self.counters.writes.load(Relaxed),
+            bytes_read: self.counters.bytes_read.load(Relaxed),
+            bytes_written: self.counters.bytes_written.load(Relaxed),
+            compactions: self.counters.compactions.load(Relaxed),
+        }
+    }
+}
+
+// Layout of a `BLOB_PAGE`'s header, reusing the same meta-header region a
+// `HEAP_PAGE` would use for its slot directory: a chunk length, then a link
+// to the next page in the chain (`BLOB_CHAIN_NIL` if this is the last one).
+const BLOB_CHUNKLEN_LOC: usize = PAGETYPE_LOC + 1;
+const BLOB_NEXT_LOC: usize = BLOB_CHUNKLEN_LOC + OFFSET_SIZE;
+const BLOB_HEADER_SIZE: usize = BLOB_NEXT_LOC + PAGEID_SIZE;
+
+/// Number of payload bytes a single `BLOB_PAGE` can carry.
+pub(crate) const BLOB_CHUNK_CAPACITY: usize = PAGE_SIZE - BLOB_HEADER_SIZE;
+
+/// Sentinel "next" pointer meaning "this is the last page in the chain".
+const BLOB_CHAIN_NIL: PageId = PageId::MAX;
+
+/// The small record a heap slot holds in place of inline bytes when the
+/// value it represents didn't fit on one page: the first page of its
+/// `BLOB_PAGE` chain, plus its total length (so a reader knows how many
+/// chunks, including a possibly short last one, to walk).
+#[derive(Serialize, Deserialize, Clone)]
+struct BlobRedirect {
+    first_page: PageId,
+    total_len: u64,
+}
+
+/// Overflow/blob pages for values larger than a single page, following
+/// Stasis's BLOB_PAGE design: a normal slot stores a `BlobRedirect` instead
+/// of inline bytes, and the payload itself lives across a chain of
+/// dedicated `BLOB_PAGE`s (see `PageType`), each carrying one
+/// `BLOB_CHUNK_CAPACITY`-sized chunk plus a link to the next. Reading or
+/// deleting a chain needs access to pages beyond `self`, which the
+/// `HeapPage` trait's single-page methods can't provide, so these take the
+/// page-id-to-`Page` lookup (backed by a `HeapFile`/`StorageManager` in
+/// practice) as a callback instead.
+impl Page {
+    /// Build one `BLOB_PAGE` holding `chunk` (must fit within
+    /// `BLOB_CHUNK_CAPACITY`) and a link to `next` (`BLOB_CHAIN_NIL` if this
+    /// is the chain's last page).
+    fn new_blob_chunk(page_id: PageId, chunk: &[u8], next: PageId) -> Self {
+        let mut page = Page::new(page_id);
+        page.update_page_type(PageType::Blob);
+        let chunk_len = chunk.len() as Offset;
+        page.data[BLOB_CHUNKLEN_LOC..BLOB_CHUNKLEN_LOC + OFFSET_SIZE]
+            .copy_from_slice(&chunk_len.to_le_bytes());
+        page.data[BLOB_NEXT_LOC..BLOB_NEXT_LOC + PAGEID_SIZE].copy_from_slice(&next.to_le_bytes());
+        page.data[BLOB_HEADER_SIZE..BLOB_HEADER_SIZE + chunk.len()].copy_from_slice(chunk);
+        page
+    }
+
+    /// Whether `slot`'s stored bytes are a `BlobRedirect` written by
+    /// `add_large_value`, rather than an inline value -- the `SLOT_COMPRESSED_FLAG`
+    /// bit's sibling in the same slot-size field. `false` for a slot that
+    /// doesn't currently hold a live value.
+    pub fn is_large_value(&self, slot: SlotId) -> bool {
+        match self.get_slot_meta_loc(slot) {
+            Some(loc) => self.slot_size_flags(loc) & SLOT_LARGE_VALUE_FLAG != 0,
+            None => false,
+        }
+    }
+
+    fn update_slot_large_value(&mut self, large: bool, loc: usize) {
+        let loc_slot_size = loc + SLOTID_SIZE;
+        let loc_endof_slot_size = loc_slot_size + OFFSET_SIZE;
+        let cleared = self.slot_size_flags(loc) & !SLOT_LARGE_VALUE_FLAG;
+        let flag = if large { SLOT_LARGE_VALUE_FLAG } else { 0 };
+        self.data[loc_slot_size..loc_endof_slot_size]
+            .copy_from_slice(&(cleared | flag).to_le_bytes());
+    }
+
+    fn slot_size_flags(&self, loc: usize) -> u16 {
+        let loc_slot_size = loc + SLOTID_SIZE;
+        let loc_endof_slot_size = loc_slot_size + OFFSET_SIZE;
+        u16::from_le_bytes(
+            self.data[loc_slot_size..loc_endof_slot_size]
+                .try_into()
+                .unwrap(),
+        )
+    }
+
+    fn blob_chunk_len(&self) -> Offset {
+        Offset::from_le_bytes(
+            self.data[BLOB_CHUNKLEN_LOC..BLOB_CHUNKLEN_LOC + OFFSET_SIZE]
+                .try_into()
+                .unwrap(),
+        )
+    }
+
+    fn blob_next_page(&self) -> PageId {
+        PageId::from_le_bytes(
+            self.data[BLOB_NEXT_LOC..BLOB_NEXT_LOC + PAGEID_SIZE]
+                .try_into()
+                .unwrap(),
+        )
+    }
+
+    fn blob_chunk_bytes(&self) -> &[u8] {
+        let len = self.blob_chunk_len() as usize;
+        &self.data[BLOB_HEADER_SIZE..BLOB_HEADER_SIZE + len]
+    }
+
+    /// Verify a `BLOB_PAGE`'s chunk-length header is within bounds; called
+    /// by `fsck` instead of the slot-directory checks, which don't apply.
+    fn fsck_blob(&self) -> Result<(), CrustyError> {
+        let chunk_len = self.blob_chunk_len() as usize;
+        if chunk_len > BLOB_CHUNK_CAPACITY {
+            return Err(CrustyError::CrustyError(format!(
+                "blob chunk length {} exceeds page capacity {}",
+                chunk_len, BLOB_CHUNK_CAPACITY
+            )));
+        }
+        Ok(())
+    }
+
+    /// Store `bytes` as a value too large to fit inline: split it across a
+    /// chain of new `BLOB_PAGE`s (ids handed out by `alloc`) and add a small
+    /// redirect record, flagged via `is_large_value`, naming the chain's
+    /// first page to this page's own slot directory. Returns the redirect's
+    /// slot id together with the chain pages, which the caller must persist
+    /// (e.g. write each one to the file at the id `alloc` returned for it)
+    /// -- a single page has no way to write pages other than itself, so
+    /// unlike `add_value`/`get_value`/`delete_value` this can't fully own
+    /// persisting the chain itself.
+    pub fn add_large_value(
+        &mut self,
+        bytes: &[u8],
+        mut alloc: impl FnMut() -> PageId,
+    ) -> Option<(SlotId, Vec<Page>)> {
+        let mut chunks: Vec<&[u8]> = bytes.chunks(BLOB_CHUNK_CAPACITY).collect();
+        if chunks.is_empty() {
+            // Chunking an empty slice yields no chunks, but the chain still
+            // needs exactly one (empty) page to redirect to.
+            chunks.push(&[]);
+        }
+
+        let mut next = BLOB_CHAIN_NIL;
+        let mut chain_pages = Vec::with_capacity(chunks.len());
+        for chunk in chunks.into_iter().rev() {
+            let page_id = alloc();
+            chain_pages.push(Self::new_blob_chunk(page_id, chunk, next));
+            next = page_id;
+        }
+        chain_pages.reverse();
+        let first_page = chain_pages[0].get_page_id();
+
+        let redirect = BlobRedirect {
+            first_page,
+            total_len: bytes.len() as u64,
+        };
+        let encoded = serde_cbor::to_vec(&redirect).ok()?;
+        let slot_id = self.add_value(&encoded)?;
+        if let Some(loc) = self.get_slot_meta_loc(slot_id) {
+            self.update_slot_large_value(true, loc);
+        }
+        Some((slot_id, chain_pages))
+    }
+
+    /// Read back a value stored by `add_large_value`, following its chain
+    /// through `read_page`.
+    pub fn get_large_value(
+        &self,
+        slot_id: SlotId,
+        read_page: impl FnMut(PageId) -> Option<Page>,
+    ) -> Option<Vec<u8>> {
+        let bytes = self.get_value(slot_id)?;
+        reassemble_large_value(&bytes, read_page)
+    }
+
+    /// Delete a value stored by `add_large_value`: frees the redirect slot
+    /// on this page, then walks the chain through `read_page` to find and
+    /// free (via `free_page`) every page in it.
+    pub fn delete_large_value(
+        &mut self,
+        slot_id: SlotId,
+        mut read_page: impl FnMut(PageId) -> Option<Page>,
+        mut free_page: impl FnMut(PageId),
+    ) -> Option<()> {
+        let bytes = self.get_value(slot_id)?;
+        let redirect: BlobRedirect = serde_cbor::from_slice(&bytes).ok()?;
+
+        let mut next = redirect.first_page;
+        while next != BLOB_CHAIN_NIL {
+            let chunk_page = read_page(next)?;
+            let after = chunk_page.blob_next_page();
+            free_page(next);
+            next = after;
+        }
+
+        self.delete_value(slot_id)
+    }
+}
+
+// Layout of a `PREFIX_PAGE`'s header, reusing the same meta-header region a
+// `HEAP_PAGE` would use for its slot directory: an entry count, then the
+// offset where the next entry's bytes should be appended. Entries themselves
+// are packed forward from `PREFIX_HEADER_SIZE`; the restart-offset array
+// (see below) grows backward from `PAGE_SIZE`, meeting it in the middle the
+// same way a `HEAP_PAGE`'s slot directory and data region do.
+const PREFIX_NUM_ENTRIES_LOC: usize = PAGETYPE_LOC + 1;
+const PREFIX_DATA_END_LOC: usize = PREFIX_NUM_ENTRIES_LOC + std::mem::size_of::<u16>();
+const PREFIX_HEADER_SIZE: usize = PREFIX_DATA_END_LOC + OFFSET_SIZE;
+
+/// Every this-many'th entry is stored as a "restart": `shared_len = 0` and
+/// its full bytes inline, so `get_value_prefix` never has to walk back more
+/// than this many entries to rebuild a prefix chain from scratch.
+const PREFIX_RESTART_INTERVAL: u16 = 16;
+
+/// Prefix-compressed, append-only storage for pages holding lexicographically
+/// ordered byte values, modeled on the block-builder scheme SSTable formats
+/// (e.g. LevelDB) use for sorted keys: each entry is encoded as
+/// `(shared_len: Offset, non_shared_len: Offset, bytes[shared_len..])`, where
+/// `shared_len` is the length of the common prefix with the previous entry.
+/// Every `PREFIX_RESTART_INTERVAL`th entry is a restart point (`shared_len`
+/// forced to 0) whose offset is recorded in a restart array at the tail of
+/// the page, so `get_value_prefix` only ever has to decode forward from the
+/// nearest preceding restart instead of from the very first entry.
+///
+/// A slot id in this mode is an entry's rank (the order it was added in),
+/// the same convention `add_value_sorted` uses and for the same reason: the
+/// scheme only holds together if entries stay in ascending order, so there's
+/// nothing to gain from a stable id independent of position. Deleting a
+/// single entry would leave every later entry's shared-prefix chain (and the
+/// restart array's offsets) referencing an invalidated layout, so this mode
+/// doesn't support `delete_value` at all -- see its dispatch below.
+impl Page {
+    /// Create a new, empty page in prefix-compressed mode.
+    pub fn new_prefix_compressed(page_id: PageId) -> Self {
+        let mut page = Page::new(page_id);
+        page.update_page_type(PageType::Prefix);
+        page.data[PREFIX_DATA_END_LOC..PREFIX_DATA_END_LOC + OFFSET_SIZE]
+            .copy_from_slice(&(PREFIX_HEADER_SIZE as Offset).to_le_bytes());
+        page
+    }
+
+    fn prefix_num_entries(&self) -> u16 {
+        u16::from_le_bytes(
+            self.data[PREFIX_NUM_ENTRIES_LOC..PREFIX_NUM_ENTRIES_LOC + 2]
+                .try_into()
+                .unwrap(),
+        )
+    }
+
+    fn update_prefix_num_entries(&mut self, new_num: u16) {
+        self.data[PREFIX_NUM_ENTRIES_LOC..PREFIX_NUM_ENTRIES_LOC + 2]
+            .copy_from_slice(&new_num.to_le_bytes());
+    }
+
+    fn prefix_data_end(&self) -> Offset {
+        Offset::from_le_bytes(
+            self.data[PREFIX_DATA_END_LOC..PREFIX_DATA_END_LOC + OFFSET_SIZE]
+                .try_into()
+                .unwrap(),
+        )
+    }
+
+    fn update_prefix_data_end(&mut self, new_end: Offset) {
+        self.data[PREFIX_DATA_END_LOC..PREFIX_DATA_END_LOC + OFFSET_SIZE]
+            .copy_from_slice(&new_end.to_le_bytes());
+    }
+
+    /// Number of restart points recorded for `num_entries` entries (one for
+    /// entry 0, then every `PREFIX_RESTART_INTERVAL`th entry after it).
+    fn prefix_restart_count(num_entries: u16) -> u16 {
+        if num_entries == 0 {
+            0
+        } else {
+            (num_entries - 1) / PREFIX_RESTART_INTERVAL + 1
+        }
+    }
+
+    /// Byte range of the restart array's `idx`th entry (0-based), counting
+    /// backward from `PAGE_SIZE` -- restart 0 is nearest the page's end.
+    fn prefix_restart_slot(idx: u16) -> std::ops::Range<usize> {
+        let end = PAGE_SIZE - idx as usize * OFFSET_SIZE;
+        (end - OFFSET_SIZE)..end
+    }
+
+    fn prefix_restart_offset(&self, idx: u16) -> Offset {
+        Offset::from_le_bytes(self.data[Self::prefix_restart_slot(idx)].try_into().unwrap())
+    }
+
+    fn push_prefix_restart_offset(&mut self, offset: Offset) {
+        let idx = Self::prefix_restart_count(self.prefix_num_entries());
+        self.data[Self::prefix_restart_slot(idx)].copy_from_slice(&offset.to_le_bytes());
+    }
+
+    /// Decode the entry starting at `at`, given the previous entry's full
+    /// bytes (ignored for a restart entry, whose `shared_len` is 0). Returns
+    /// the reconstructed bytes and the offset just past this entry.
+    fn decode_prefix_entry(&self, at: usize, prev: &[u8]) -> (Vec<u8>, usize) {
+        let shared_len =
+            Offset::from_le_bytes(self.data[at..at + OFFSET_SIZE].try_into().unwrap()) as usize;
+        let non_shared_len =
+            Offset::from_le_bytes(self.data[at + OFFSET_SIZE..at + 2 * OFFSET_SIZE].try_into().unwrap())
+                as usize;
+        let non_shared_start = at + 2 * OFFSET_SIZE;
+        let non_shared_end = non_shared_start + non_shared_len;
+        let mut bytes = Vec::with_capacity(shared_len + non_shared_len);
+        bytes.extend_from_slice(&prev[..shared_len]);
+        bytes.extend_from_slice(&self.data[non_shared_start..non_shared_end]);
+        (bytes, non_shared_end)
+    }
+
+    /// Insert `bytes`, which must sort at or after every entry already on
+    /// the page. Returns the new entry's rank (its slot id), or `None` if
+    /// it's out of order or the page is full.
+    fn add_value_prefix(&mut self, bytes: &[u8]) -> Option<SlotId> {
+        let num_entries = self.prefix_num_entries();
+        let is_restart = num_entries % PREFIX_RESTART_INTERVAL == 0;
+
+        let shared_len = if is_restart {
+            0
+        } else {
+            let prev = self.get_value_prefix(num_entries - 1)?;
+            if bytes < prev.as_slice() {
+                // Out of order: the scheme only holds together for
+                // non-decreasing input, so reject rather than silently
+                // corrupting every later entry's prefix chain.
+                return None;
+            }
+            common_prefix_len(&prev, bytes)
+        };
+        let non_shared = &bytes[shared_len..];
+        let entry_size = 2 * OFFSET_SIZE + non_shared.len();
+        let restart_size = if is_restart { OFFSET_SIZE } else { 0 };
+
+        let data_end = self.prefix_data_end() as usize;
+        let restart_start = PAGE_SIZE - Self::prefix_restart_count(num_entries) as usize * OFFSET_SIZE;
+        if data_end + entry_size + restart_size > restart_start {
+            return None;
+        }
+
+        let at = data_end;
+        self.data[at..at + OFFSET_SIZE].copy_from_slice(&(shared_len as Offset).to_le_bytes());
+        self.data[at + OFFSET_SIZE..at + 2 * OFFSET_SIZE]
+            .copy_from_slice(&(non_shared.len() as Offset).to_le_bytes());
+        self.data[at + 2 * OFFSET_SIZE..at + entry_size].copy_from_slice(non_shared);
+
+        if is_restart {
+            self.push_prefix_restart_offset(at as Offset);
+        }
+        self.update_prefix_data_end((at + entry_size) as Offset);
+        self.update_prefix_num_entries(num_entries + 1);
+
+        Some(num_entries as SlotId)
+    }
+
+    /// Read back the entry at rank `slot_id`, reconstructing it from the
+    /// nearest preceding restart point.
+    fn get_value_prefix(&self, slot_id: SlotId) -> Option<Vec<u8>> {
+        let num_entries = self.prefix_num_entries();
+        if slot_id >= num_entries {
+            return None;
+        }
+        let restart_idx = slot_id / PREFIX_RESTART_INTERVAL;
+        let mut at = self.prefix_restart_offset(restart_idx) as usize;
+
+        let mut current = Vec::new();
+        for _ in (restart_idx * PREFIX_RESTART_INTERVAL)..=slot_id {
+            let (bytes, next_at) = self.decode_prefix_entry(at, &current);
+            current = bytes;
+            at = next_at;
+        }
+        Some(current)
+    }
+
+    /// Verify a `PREFIX_PAGE`'s entry data hasn't grown past its restart
+    /// array; called by `fsck` instead of the slot-directory checks, which
+    /// don't apply.
+    fn fsck_prefix(&self) -> Result<(), CrustyError> {
+        let num_entries = self.prefix_num_entries();
+        let restart_start = PAGE_SIZE - Self::prefix_restart_count(num_entries) as usize * OFFSET_SIZE;
+        if self.prefix_data_end() as usize > restart_start {
+            return Err(CrustyError::CrustyError(format!(
+                "prefix page data end {} overlaps restart array starting at {}",
+                self.prefix_data_end(),
+                restart_start
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// Length of the common prefix shared by `a` and `b`.
+fn common_prefix_len(a: &[u8], b: &[u8]) -> usize {
+    a.iter().zip(b.iter()).take_while(|(x, y)| x == y).count()
+}
+
+/// Wraps a value with the key it was inserted under in a sorted-mode page
+/// (see `Page::add_value_sorted`), stored transparently as the payload of a
+/// normal slot so the surrounding slot-directory layout is untouched.
+#[derive(Serialize, Deserialize, Clone)]
+struct SortedRecord {
+    key: Vec<u8>,
+    bytes: Vec<u8>,
+}
+
+/// An opt-in ordered mode, inspired by InnoDB's `page0cur` page directory:
+/// `add_value_sorted` keeps the slot-header array itself in ascending key
+/// order, so `search` can binary-search it in O(log n) instead of scanning
+/// every slot. Only the fixed 6-byte slot-header entries move to stay
+/// sorted -- the variable-length data they point at is stored whereever
+/// plain `add_value` put it and never moves just to maintain order (though
+/// it may still move if a `compact()` pass runs to reclaim fragmented
+/// space; that only changes offsets, not header positions).
+///
+/// A page's slot ids double as their rank in the ordered array here, so
+/// (unlike plain heap mode) inserting a new key can shift the id of an
+/// existing entry. Callers that need a stable handle across inserts should
+/// re-`search` for it rather than cache a returned id. This mode also
+/// assumes every slot on the page was added via `add_value_sorted` and
+/// never deleted: it always appends (never consults the intrusive free
+/// list), so mixing it with `delete_value` would leave a hole this
+/// position-is-rank assumption doesn't account for.
+impl Page {
+    /// Insert `bytes` keyed by `key`, shifting slot headers as needed to
+    /// keep the array in ascending key order. Returns the slot id (i.e.
+    /// rank) the entry landed at, or `None` if the page is full.
+    pub fn add_value_sorted(&mut self, key: &[u8], bytes: &[u8]) -> Option<SlotId> {
+        let record = SortedRecord {
+            key: key.to_vec(),
+            bytes: bytes.to_vec(),
+        };
+        let encoded = serde_cbor::to_vec(&record).ok()?;
+        // Search before appending: `search`'s binary search assumes the
+        // header array it's scanning is fully ordered, which is only true
+        // of the slots that existed *before* this insert -- the one
+        // `add_value` is about to append lands at `get_num_slots()` and
+        // hasn't been placed in order yet.
+        let dest = match self.search(key) {
+            Ok(pos) | Err(pos) => pos,
+        };
+        // `add_value` always appends a brand new header at the current
+        // `get_num_slots()` position (sorted-mode pages never delete, so the
+        // free list this would otherwise consult is always empty).
+        let appended_at = self.add_value(&encoded)?;
+        if dest != appended_at {
+            self.shift_slot_header_down(dest, appended_at);
+        }
+        Some(dest)
+    }
+
+    /// Binary searches the ordered slot-header array for `key`. Returns
+    /// `Ok(slot)` naming an exact match, or `Err(slot)` with the position a
+    /// new entry should be inserted at to keep the array ordered.
+    pub fn search(&self, key: &[u8]) -> Result<SlotId, SlotId> {
+        let mut lo: SlotId = 0;
+        let mut hi: SlotId = self.get_num_slots();
+        while lo < hi {
+            let mid = lo + (hi - lo) / 2;
+            match self.sorted_key_at(mid) {
+                Some(mid_key) if mid_key == key => return Ok(mid),
+                Some(mid_key) if mid_key.as_slice() < key => lo = mid + 1,
+                _ => hi = mid,
+            }
+        }
+        Err(lo)
+    }
+
+    fn sorted_key_at(&self, slot: SlotId) -> Option<Vec<u8>> {
+        let bytes = self.get_value(slot)?;
+        let record: SortedRecord = serde_cbor::from_slice(&bytes).ok()?;
+        Some(record.key)
+    }
+
+    /// Moves the header entry at `from` (always the just-appended, highest
+    /// position) down to `to`, shifting everything in between up by one
+    /// slot. Only the fixed-size header bytes move.
+    fn shift_slot_header_down(&mut self, to: SlotId, from: SlotId) {
+        let to_loc = META_HEADER_SIZE + (to as usize * SLOT_META_SIZE);
+        let from_loc = META_HEADER_SIZE + (from as usize * SLOT_META_SIZE);
+        let mut moved = [0u8; SLOT_META_SIZE];
+        moved.copy_from_slice(&self.data[from_loc..from_loc + SLOT_META_SIZE]);
+        self.data
+            .copy_within(to_loc..from_loc, to_loc + SLOT_META_SIZE);
+        self.data[to_loc..to_loc + SLOT_META_SIZE].copy_from_slice(&moved);
+    }
+}
+
+/// Decode a `BlobRedirect` from `encoded` (the raw bytes of a slot flagged
+/// `is_large_value`) and walk its chain through `read_page`, reassembling
+/// the original value. Shared by `Page::get_large_value` (which owns the
+/// redirect's page) and the raw zero-copy scan below (which only has the
+/// encoded bytes in hand, borrowed out of a mapped page with no owned
+/// `Page` to call `get_large_value` on).
+pub(crate) fn reassemble_large_value(
+    encoded: &[u8],
+    mut read_page: impl FnMut(PageId) -> Option<Page>,
+) -> Option<Vec<u8>> {
+    let redirect: BlobRedirect = serde_cbor::from_slice(encoded).ok()?;
+    let mut out = Vec::with_capacity(redirect.total_len as usize);
+    let mut next = redirect.first_page;
+    while next != BLOB_CHAIN_NIL {
+        let chunk_page = read_page(next)?;
+        out.extend_from_slice(chunk_page.blob_chunk_bytes());
+        next = chunk_page.blob_next_page();
+    }
+    Some(out)
+}
+
+/// Zero-copy helpers that interpret a page's raw bytes (e.g. a slice
+/// borrowed directly from a memory-mapped `HeapFile`) without requiring an
+/// owned `Page`. These mirror the slot-directory layout read by the
+/// `HeapPage` trait above, but return borrows into `data` instead of a
+/// cloned page, so a caller walking many slots only copies out the bytes it
+/// actually keeps.
+pub(crate) fn raw_total_slot_headers(data: &[u8]) -> u16 {
+    u16::from_le_bytes(data[TOTSLOTS_LOC..SLOTSTART_LOC].try_into().unwrap())
+}
+
+pub(crate) fn raw_slot_meta_loc(data: &[u8], slot: SlotId) -> Option<usize> {
+    if slot >= raw_total_slot_headers(data) {
+        return None;
+    }
+    let loc = META_HEADER_SIZE + (slot as usize * SLOT_META_SIZE);
+    let loc_slot_size = loc + SLOTID_SIZE;
+    let size = u16::from_le_bytes(
+        data[loc_slot_size..loc_slot_size + OFFSET_SIZE]
+            .try_into()
+            .unwrap(),
+    );
+    if size == 0 {
+        // Free (on the intrusive free list), not a live value.
+        return None;
+    }
+    Some(loc)
+}
+
+/// Whether `slot`'s raw bytes are a `BlobRedirect` written by
+/// `Page::add_large_value`, mirroring `Page::is_large_value` but reading
+/// straight out of `data` instead of through an owned `Page`.
+pub(crate) fn raw_is_large_value(data: &[u8], slot: SlotId) -> bool {
+    match raw_slot_meta_loc(data, slot) {
+        Some(loc) => {
+            let loc_slot_size = loc + SLOTID_SIZE;
+            let raw_size = u16::from_le_bytes(
+                data[loc_slot_size..loc_slot_size + OFFSET_SIZE]
+                    .try_into()
+                    .unwrap(),
+            );
+            raw_size & SLOT_LARGE_VALUE_FLAG != 0
+        }
+        None => false,
+    }
+}
+
+/// Returns the bytes for `slot`, borrowed directly out of `data` when they
+/// are stored inline, or decompressed into a fresh `Vec` when `add_value`
+/// stored them LZ4-compressed (see `SLOT_COMPRESSED_FLAG`) -- a plain
+/// borrow can't stand in for compressed bytes, so this only stays zero-copy
+/// for the uncompressed case.
+pub(crate) fn raw_slot_value(data: &[u8], slot: SlotId) -> Option<std::borrow::Cow<[u8]>> {
+    let slotmetaloc = raw_slot_meta_loc(data, slot)?;
+    let loc_slot_size = slotmetaloc + SLOTID_SIZE;
+    let raw_size = u16::from_le_bytes(
+        data[loc_slot_size..loc_slot_size + OFFSET_SIZE]
+            .try_into()
+            .unwrap(),
+    );
+    let size = (raw_size & SLOT_SIZE_MASK) as usize;
+    let is_compressed = raw_size & SLOT_COMPRESSED_FLAG != 0;
+    let loc_slot_offset = loc_slot_size + OFFSET_SIZE;
+    let offset = Offset::from_le_bytes(
+        data[loc_slot_offset..loc_slot_offset + OFFSET_SIZE]
+            .try_into()
+            .unwrap(),
+    ) as usize;
+    let stored = &data[(offset - size)..offset];
+    if is_compressed {
+        lz4_flex::decompress_size_prepended(stored)
+            .ok()
+            .map(std::borrow::Cow::Owned)
+    } else {
+        Some(std::borrow::Cow::Borrowed(stored))
+    }
+}
+
+/// Scans forward from `start_slot` (inclusive) for the next in-use slot,
+/// returning its bytes, id, and the slot id to resume scanning from.
+pub(crate) fn raw_next_value(
+    data: &[u8],
+    start_slot: SlotId,
+) -> Option<(std::borrow::Cow<[u8]>, SlotId, SlotId)> {
+    let total = raw_total_slot_headers(data);
+    let mut slot = start_slot;
+    while slot < total {
+        if let Some(bytes) = raw_slot_value(data, slot) {
+            return Some((bytes, slot, slot + 1));
+        }
+        slot += 1;
+    }
+    None
+}
+
+/// Like `raw_next_value`, but decodes each candidate slot as a
+/// `VersionedRecord` and skips any that `snapshot` should not see, so an
+/// MVCC-aware scan can walk slots by borrowing straight out of a mapped
+/// page without materializing an owned `Page`.
+pub(crate) fn raw_next_visible_value(
+    data: &[u8],
+    start_slot: SlotId,
+    snapshot: &Snapshot,
+) -> Option<(Vec<u8>, SlotId, SlotId)> {
+    let total = raw_total_slot_headers(data);
+    let mut slot = start_slot;
+    while slot < total {
+        if let Some(bytes) = raw_slot_value(data, slot) {
+            if let Ok(record) = serde_cbor::from_slice::<VersionedRecord>(&bytes) {
+                if snapshot.is_visible(&record) {
+                    return Some((record.bytes, slot, slot + 1));
+                }
+            }
+        }
+        slot += 1;
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::VecDeque;
+
+    use super::*;
+    use common::testutil::init;
+    use common::testutil::*;
+    use common::Tuple;
+    use rand::Rng;
+
+    /// Limits how on how many bytes we can use for page metadata / header
+    pub const FIXED_HEADER_SIZE: usize = 13;
+    pub const HEADER_PER_VAL_SIZE: usize = 6;
+
+    #[test]
+    fn hs_page_sizes_header_free_space() {
+        init();
+        let p = Page::new(0);
+        assert_eq!(0, p.get_page_id());
+
+        assert_eq!(PAGE_SIZE - p.get_header_size(), p.get_free_space());
+    }
+
+    #[test]
+    fn hs_page_debug_insert() {
+        init();
+        let mut p = Page::new(0);
+        let n = 20;
+        let size = 20;
+        let vals = get_ascending_vec_of_byte_vec_02x(n, size, size);
+        for x in &vals {
+            p.add_value(x);
+        }
+        assert_eq!(
+            p.get_free_space(),
+            PAGE_SIZE - p.get_header_size() - n * size
+        );
+    }
+
+    #[test]
+    fn hs_page_simple_insert() {
+        init();
+        let mut p = Page::new(5);
+        let tuple = int_vec_to_tuple(vec![0, 1, 2]);
+        let tuple_bytes = serde_cbor::to_vec(&tuple).unwrap();
+        let byte_len = tuple_bytes.len();
+        assert_eq!(Some(0), p.add_value(&tuple_bytes));
+        assert_eq!(
+            PAGE_SIZE - byte_len - p.get_header_size(),
+            p.get_free_space()
+        );
+        let tuple_bytes2 = serde_cbor::to_vec(&tuple).unwrap();
+        assert_eq!(Some(1), p.add_value(&tuple_bytes2));
+        assert_eq!(
+            PAGE_SIZE - p.get_header_size() - byte_len - byte_len,
+            p.get_free_space()
+        );
+    }
+
+    #[test]
+    fn hs_page_space() {
+        init();
+        let mut p = Page::new(0);
+        let size = 10;
+        let bytes = get_random_byte_vec(size);
+        assert_eq!(10, bytes.len());
+        assert_eq!(Some(0), p.add_value(&bytes));
+        assert_eq!(PAGE_SIZE - p.get_header_size() - size, p.get_free_space());
+        assert_eq!(Some(1), p.add_value(&bytes));
+        assert_eq!(
+            PAGE_SIZE - p.get_header_size() - size * 2,
+            p.get_free_space()
+        );
+        assert_eq!(Some(2), p.add_value(&bytes));
+        assert_eq!(
+            PAGE_SIZE - p.get_header_size() - size * 3,
+            p.get_free_space()
+        );
+    }
+
+    #[test]
+    fn hs_page_get_value() {
+        init();
+        let mut p = Page::new(0);
+
+        let tuple = int_vec_to_tuple(vec![0, 1, 2]);
+        let tuple_bytes = serde_cbor::to_vec(&tuple).unwrap();
+        assert_eq!(Some(0), p.add_value(&tuple_bytes));
+
+        let check_bytes = p.get_value(0).unwrap();
+        let check_tuple: Tuple = serde_cbor::from_slice(&check_bytes).unwrap();
+        assert_eq!(tuple_bytes, check_bytes);
+        assert_eq!(tuple, check_tuple);
 
         let tuple2 = int_vec_to_tuple(vec![3, 3, 3]);
         let tuple_bytes2 = serde_cbor::to_vec(&tuple2).unwrap();
@@ -873,8 +2475,10 @@ mod tests {
         assert_eq!(None, p.get_value(1));
         assert_eq!(Some(()), p.delete_value(6));
         assert_eq!(None, p.get_value(6));
-        assert_eq!(Some(1), p.add_value(&larger_val));
-        assert_eq!(larger_val, p.get_value(1).unwrap());
+        // Slot ids are reused LIFO off the free list, so the most recently
+        // deleted slot (6) comes back before the earlier one (1).
+        assert_eq!(Some(6), p.add_value(&larger_val));
+        assert_eq!(larger_val, p.get_value(6).unwrap());
     }
 
     #[test]
@@ -890,17 +2494,85 @@ mod tests {
             get_random_byte_vec(size / 4),
         ];
         let mut p = Page::new(0);
-        assert_eq!(Some(0), p.add_value(&values[0]));
-        assert_eq!(Some(1), p.add_value(&values[1]));
-        assert_eq!(Some(2), p.add_value(&values[2]));
-        assert_eq!(Some(3), p.add_value(&values[3]));
-        assert_eq!(Some(4), p.add_value(&values[4]));
-        assert_eq!(values[0], p.get_value(0).unwrap());
-        assert_eq!(None, p.add_value(&values[0]));
-        assert_eq!(Some(()), p.delete_value(1));
-        assert_eq!(None, p.get_value(1));
-        assert_eq!(Some(1), p.add_value(&values[5]));
-        assert_eq!(values[5], p.get_value(1).unwrap());
+        assert_eq!(Some(0), p.add_value(&values[0]));
+        assert_eq!(Some(1), p.add_value(&values[1]));
+        assert_eq!(Some(2), p.add_value(&values[2]));
+        assert_eq!(Some(3), p.add_value(&values[3]));
+        assert_eq!(Some(4), p.add_value(&values[4]));
+        assert_eq!(values[0], p.get_value(0).unwrap());
+        assert_eq!(None, p.add_value(&values[0]));
+        assert_eq!(Some(()), p.delete_value(1));
+        assert_eq!(None, p.get_value(1));
+        assert_eq!(Some(1), p.add_value(&values[5]));
+        assert_eq!(values[5], p.get_value(1).unwrap());
+    }
+
+    #[test]
+    fn hs_page_delete_defers_compaction() {
+        init();
+        let mut p = Page::new(0);
+        let bytes = get_random_byte_vec(100);
+        p.add_value(&bytes).unwrap();
+        let slot = p.add_value(&bytes).unwrap();
+        p.add_value(&bytes).unwrap();
+
+        let first_offset_before = p.get_first_offset();
+        assert_eq!(0, p.get_fragmented_space());
+
+        // A delete should just mark the hole as fragmented, not shift any
+        // other slot's data or touch first_offset.
+        assert_eq!(Some(()), p.delete_value(slot));
+        assert_eq!(bytes.len() as u16, p.get_fragmented_space());
+        assert_eq!(first_offset_before, p.get_first_offset());
+    }
+
+    #[test]
+    fn hs_page_add_value_only_compacts_when_contiguous_space_is_insufficient() {
+        init();
+        let mut p = Page::new(0);
+        let size = 800;
+        let values: Vec<Vec<u8>> = (0..5).map(|_| get_random_byte_vec(size)).collect();
+        for v in &values {
+            p.add_value(v).unwrap();
+        }
+        // Enough fragmented room exists for a small value to fit in the
+        // leftover contiguous space without needing to reclaim it.
+        let deleted_slot = 0;
+        p.delete_value(deleted_slot).unwrap();
+        assert_eq!(size as u16, p.get_fragmented_space());
+
+        let tiny = get_random_byte_vec(10);
+        p.add_value(&tiny).unwrap();
+        // Fits in the page's small leftover contiguous margin -- no need to
+        // touch the fragmented hole left by the delete above.
+        assert_eq!(size as u16, p.get_fragmented_space());
+
+        // This one only fits once the fragmented hole is reclaimed.
+        let needs_compaction = get_random_byte_vec(size - 20);
+        p.add_value(&needs_compaction).unwrap();
+        assert_eq!(0, p.get_fragmented_space());
+    }
+
+    #[test]
+    fn hs_page_interleaved_delete_insert_preserves_data_integrity() {
+        init();
+        let mut p = Page::new(0);
+        let mut live: Vec<(SlotId, Vec<u8>)> = Vec::new();
+
+        for round in 0..20 {
+            let bytes = get_random_byte_vec(50 + (round % 7) * 10);
+            if let Some(slot) = p.add_value(&bytes) {
+                live.push((slot, bytes));
+            }
+            if round % 3 == 0 && !live.is_empty() {
+                let (slot, _) = live.remove(0);
+                assert_eq!(Some(()), p.delete_value(slot));
+            }
+            assert!(p.fsck().is_ok());
+            for (slot, expected) in &live {
+                assert_eq!(Some(expected.clone()), p.get_value(*slot));
+            }
+        }
     }
 
     #[test]
@@ -1017,4 +2689,630 @@ mod tests {
             }
         }
     }
+
+    #[test]
+    fn hs_page_mvcc_snapshot_hides_uncommitted_and_deleted() {
+        init();
+        let mut p = Page::new(0);
+        let writer = TransactionId::new();
+        let reader = TransactionId::new();
+
+        let slot = p.add_value_mvcc(b"v1", writer).unwrap();
+
+        // Not yet committed: invisible even though the reader's own tid
+        // would see its own writes.
+        let mut committed = std::collections::HashSet::new();
+        let snapshot = Snapshot::new(reader, &committed);
+        assert_eq!(p.get_value_mvcc(slot, &snapshot), None);
+
+        // Committed before the snapshot is taken: now visible.
+        committed.insert(writer);
+        let snapshot = Snapshot::new(reader, &committed);
+        assert_eq!(p.get_value_mvcc(slot, &snapshot), Some(b"v1".to_vec()));
+
+        // Deleted by a committed transaction: invisible again.
+        let deleter = TransactionId::new();
+        let slot = p.delete_value_mvcc(slot, deleter).unwrap();
+        committed.insert(deleter);
+        let snapshot = Snapshot::new(reader, &committed);
+        assert_eq!(p.get_value_mvcc(slot, &snapshot), None);
+    }
+
+    #[test]
+    fn hs_page_mvcc_own_writer_sees_its_own_uncommitted_write() {
+        init();
+        let mut p = Page::new(0);
+        let writer = TransactionId::new();
+        let slot = p.add_value_mvcc(b"v1", writer).unwrap();
+
+        let committed = std::collections::HashSet::new();
+        let snapshot = Snapshot::new(writer, &committed);
+        assert_eq!(p.get_value_mvcc(slot, &snapshot), Some(b"v1".to_vec()));
+    }
+
+    /// Walks the intrusive free list starting at the page's freelist head,
+    /// following each freed slot's stored "next" pointer, and returns its
+    /// length.
+    fn freelist_len(p: &Page) -> usize {
+        let mut len = 0;
+        let mut cur = p.get_freelist_head();
+        while cur != FREELIST_NIL {
+            len += 1;
+            let loc = META_HEADER_SIZE + (cur as usize * SLOT_META_SIZE);
+            cur = p.get_slot_offset(cur, loc);
+        }
+        len
+    }
+
+    #[test]
+    fn hs_page_freelist_len_matches_total_minus_live_slots() {
+        init();
+        let mut p = Page::new(0);
+        let bytes = get_random_byte_vec(20);
+
+        let check_invariant = |p: &Page| {
+            assert_eq!(
+                freelist_len(p),
+                (p.get_total_slot_headers() - p.get_num_slots()) as usize
+            );
+        };
+        check_invariant(&p);
+
+        let mut live: Vec<SlotId> = Vec::new();
+        for _ in 0..8 {
+            live.push(p.add_value(&bytes).unwrap());
+            check_invariant(&p);
+        }
+
+        // Delete every other slot, then refill some of the holes.
+        let mut i = 0;
+        while i < live.len() {
+            p.delete_value(live.remove(i)).unwrap();
+            check_invariant(&p);
+            i += 1;
+        }
+        for _ in 0..2 {
+            live.push(p.add_value(&bytes).unwrap());
+            check_invariant(&p);
+        }
+        for slot in live {
+            p.delete_value(slot).unwrap();
+            check_invariant(&p);
+        }
+    }
+
+    #[test]
+    fn hs_page_fsck_passes_on_inserts_and_deletes() {
+        init();
+        let mut p = Page::new(0);
+        assert!(p.fsck().is_ok());
+
+        let bytes = get_random_byte_vec(50);
+        let slots: Vec<SlotId> = (0..5).map(|_| p.add_value(&bytes).unwrap()).collect();
+        assert!(p.fsck().is_ok());
+
+        p.delete_value(slots[1]).unwrap();
+        p.delete_value(slots[3]).unwrap();
+        assert!(p.fsck().is_ok());
+
+        p.add_value(&get_random_byte_vec(10)).unwrap();
+        assert!(p.fsck().is_ok());
+    }
+
+    #[test]
+    fn hs_page_fsck_catches_overlapping_slot_ranges() {
+        init();
+        let mut p = Page::new(0);
+        let bytes = get_random_byte_vec(50);
+        let slot0 = p.add_value(&bytes).unwrap();
+        let _slot1 = p.add_value(&bytes).unwrap();
+        assert!(p.fsck().is_ok());
+
+        // Slot0 occupies the higher addresses; grow slot0's claimed size so
+        // its range reaches down into slot1's without touching either offset
+        // or dropping below `first_offset` (so this exercises the overlap
+        // check specifically, not the bounds checks).
+        let loc0 = META_HEADER_SIZE + (slot0 as usize * SLOT_META_SIZE);
+        let grown_size = p.get_slot_size(slot0, loc0) + 10;
+        p.update_slot_size(slot0, grown_size, loc0);
+
+        assert!(p.fsck().is_err());
+    }
+
+    /// A tiny stand-in for the chain-page storage a `HeapFile` would
+    /// actually provide, used to drive `*_large_value` through their callbacks.
+    fn blob_store(chain_pages: Vec<Page>) -> std::collections::HashMap<PageId, Page> {
+        chain_pages
+            .into_iter()
+            .map(|p| (p.get_page_id(), p))
+            .collect()
+    }
+
+    #[test]
+    fn hs_page_blob_roundtrip_single_chunk() {
+        init();
+        let mut p = Page::new(0);
+        let value = get_random_byte_vec(100);
+
+        let mut next_id: PageId = 1;
+        let (slot, chain_pages) = p
+            .add_large_value(&value, || {
+                let id = next_id;
+                next_id += 1;
+                id
+            })
+            .unwrap();
+        assert_eq!(chain_pages.len(), 1);
+        assert_eq!(p.get_page_type(), PageType::Heap);
+        assert_eq!(chain_pages[0].get_page_type(), PageType::Blob);
+
+        let store = blob_store(chain_pages);
+        let read_back = p
+            .get_large_value(slot, |id| store.get(&id).cloned())
+            .unwrap();
+        assert_eq!(read_back, value);
+    }
+
+    #[test]
+    fn hs_page_blob_roundtrip_multi_chunk_chain() {
+        init();
+        let mut p = Page::new(0);
+        let value = get_random_byte_vec(BLOB_CHUNK_CAPACITY * 3 + 17);
+
+        let mut next_id: PageId = 1;
+        let (slot, chain_pages) = p
+            .add_large_value(&value, || {
+                let id = next_id;
+                next_id += 1;
+                id
+            })
+            .unwrap();
+        assert_eq!(chain_pages.len(), 4);
+        for page in &chain_pages {
+            assert_eq!(page.get_page_type(), PageType::Blob);
+            assert!(page.fsck().is_ok());
+        }
+
+        let store = blob_store(chain_pages);
+        let read_back = p
+            .get_large_value(slot, |id| store.get(&id).cloned())
+            .unwrap();
+        assert_eq!(read_back, value);
+    }
+
+    #[test]
+    fn hs_page_blob_delete_frees_whole_chain() {
+        init();
+        let mut p = Page::new(0);
+        let value = get_random_byte_vec(BLOB_CHUNK_CAPACITY * 2 + 5);
+
+        let mut next_id: PageId = 1;
+        let (slot, chain_pages) = p
+            .add_large_value(&value, || {
+                let id = next_id;
+                next_id += 1;
+                id
+            })
+            .unwrap();
+        let mut store = blob_store(chain_pages);
+
+        let mut freed = Vec::new();
+        p.delete_large_value(
+            slot,
+            |id| store.get(&id).cloned(),
+            |id| freed.push(id),
+        )
+        .unwrap();
+
+        assert_eq!(freed.len(), store.len());
+        // Redirect slot itself is gone too.
+        assert_eq!(None, p.get_value(slot));
+        for id in freed {
+            store.remove(&id);
+        }
+        assert!(store.is_empty());
+    }
+
+    #[test]
+    fn hs_page_blob_page_is_skipped_by_heap_iterator() {
+        init();
+        let blob = Page::new_blob_chunk(7, b"chunk bytes", BLOB_CHAIN_NIL);
+        assert_eq!(blob.get_page_type(), PageType::Blob);
+        assert_eq!(None, blob.into_iter().next());
+    }
+
+    #[test]
+    fn hs_page_compressible_value_is_stored_compressed_and_roundtrips() {
+        init();
+        let mut p = Page::new(0);
+        let value = vec![7u8; 1000]; // highly repetitive, LZ4 shrinks it a lot
+
+        let slot = p.add_value(&value).unwrap();
+        let slotloc = p.get_slot_meta_loc(slot).unwrap();
+        assert!(p.is_slot_compressed(slot, slotloc));
+        assert!((p.get_slot_size(slot, slotloc) as usize) < value.len());
+        assert_eq!(Some(value), p.get_value(slot));
+    }
+
+    #[test]
+    fn hs_page_incompressible_value_falls_back_to_uncompressed() {
+        init();
+        let mut p = Page::new(0);
+        let value = get_random_byte_vec(200);
+
+        let slot = p.add_value(&value).unwrap();
+        let slotloc = p.get_slot_meta_loc(slot).unwrap();
+        assert!(!p.is_slot_compressed(slot, slotloc));
+        assert_eq!(p.get_slot_size(slot, slotloc) as usize, value.len());
+        assert_eq!(Some(value), p.get_value(slot));
+    }
+
+    #[test]
+    fn hs_page_compression_raises_effective_capacity() {
+        init();
+        let mut p = Page::new(0);
+        let value = vec![9u8; 1000];
+
+        // Compressed, many more of these fit than the raw size would allow.
+        let mut count = 0;
+        while p.add_value(&value).is_some() {
+            count += 1;
+        }
+        assert!(count > PAGE_SIZE / value.len());
+    }
+
+    #[test]
+    fn hs_page_compressed_value_survives_byte_serialize_roundtrip() {
+        init();
+        let mut p = Page::new(0);
+        let value = vec![3u8; 500];
+        let slot = p.add_value(&value).unwrap();
+
+        let p2 = Page::from_bytes(*p.to_bytes());
+        assert_eq!(Some(value), p2.get_value(slot));
+    }
+
+    #[test]
+    fn hs_page_compressed_value_is_cleared_from_freelist_correctly() {
+        init();
+        let mut p = Page::new(0);
+        let compressible = vec![5u8; 1000];
+        let incompressible = get_random_byte_vec(50);
+
+        let slot = p.add_value(&compressible).unwrap();
+        p.delete_value(slot).unwrap();
+
+        // Reusing the freed slot id with an incompressible value must not
+        // leave the old compressed flag lying around.
+        let reused = p.add_value(&incompressible).unwrap();
+        assert_eq!(reused, slot);
+        let slotloc = p.get_slot_meta_loc(reused).unwrap();
+        assert!(!p.is_slot_compressed(reused, slotloc));
+        assert_eq!(Some(incompressible), p.get_value(reused));
+    }
+
+    #[test]
+    fn hs_page_identity_compressor_is_byte_for_byte_with_uncompressed_to_bytes() {
+        init();
+        let mut p = Page::new(0);
+        p.add_value(b"hello").unwrap();
+        p.add_value(b"world").unwrap();
+
+        // Identity (id 0) is the default -- no `set_compressor` call needed
+        // to get today's existing format.
+        assert_eq!(p.get_compressor_id(), IDENTITY_COMPRESSOR_ID);
+        // Every byte except the checksum field `to_bytes` stamps last.
+        let bytes = p.to_bytes();
+        assert_eq!(bytes[..CHECKSUM_LOC], p.data[..CHECKSUM_LOC]);
+        assert_eq!(
+            bytes[CHECKSUM_LOC + CHECKSUM_SIZE..],
+            p.data[CHECKSUM_LOC + CHECKSUM_SIZE..]
+        );
+    }
+
+    #[test]
+    fn hs_page_zlib_compressor_shrinks_to_bytes_and_roundtrips() {
+        init();
+        let mut p = Page::new(0);
+        let value = vec![6u8; 1000]; // highly repetitive, zlib shrinks it a lot
+        let slot = p.add_value(&value).unwrap();
+        p.set_compressor(ZLIB_COMPRESSOR_ID);
+
+        let compressed = p.to_bytes();
+        assert_ne!(*compressed, *p.data.clone());
+
+        let p2 = Page::from_bytes(*compressed);
+        assert_eq!(p2.get_page_id(), 0);
+        assert_eq!(Some(value), p2.get_value(slot));
+    }
+
+    #[test]
+    fn hs_page_zlib_compressor_falls_back_to_identity_when_it_would_grow() {
+        init();
+        let mut p = Page::new(0);
+        let value = get_random_byte_vec(50); // incompressible, too small to help
+        let slot = p.add_value(&value).unwrap();
+        p.set_compressor(ZLIB_COMPRESSOR_ID);
+
+        let bytes = p.to_bytes();
+        assert_eq!(bytes[COMPRESSOR_ID_LOC], IDENTITY_COMPRESSOR_ID);
+
+        let p2 = Page::from_bytes(*bytes);
+        assert_eq!(Some(value), p2.get_value(slot));
+    }
+
+    #[test]
+    fn hs_page_bloom_filter_has_no_false_negatives() {
+        init();
+        let mut p = Page::new(0);
+        let values: Vec<Vec<u8>> = (0..20).map(|i| format!("value-{}", i).into_bytes()).collect();
+        for value in &values {
+            p.add_value(value).unwrap();
+        }
+
+        for value in &values {
+            assert!(p.may_contain(value));
+        }
+        assert!(!p.may_contain(b"definitely-not-inserted"));
+    }
+
+    #[test]
+    fn hs_page_bloom_filter_drops_deleted_value_after_rebuild() {
+        init();
+        let mut p = Page::new(0);
+        let kept = b"kept-value".to_vec();
+        let removed = b"removed-value".to_vec();
+        p.add_value(&kept).unwrap();
+        let removed_slot = p.add_value(&removed).unwrap();
+
+        p.delete_value(removed_slot).unwrap();
+
+        assert!(p.may_contain(&kept));
+        assert!(!p.may_contain(&removed));
+    }
+
+    #[test]
+    fn hs_page_bloom_filter_survives_byte_serialize_roundtrip() {
+        init();
+        let mut p = Page::new(0);
+        let value = b"roundtrip-me".to_vec();
+        p.add_value(&value).unwrap();
+
+        let p2 = Page::from_bytes(*p.to_bytes());
+        assert!(p2.may_contain(&value));
+        assert!(!p2.may_contain(b"never-added"));
+    }
+
+    #[test]
+    fn hs_page_prefix_mode_roundtrips_ordered_values_across_restarts() {
+        init();
+        let mut p = Page::new_prefix_compressed(0);
+        let values: Vec<Vec<u8>> = (0..50)
+            .map(|i| format!("key{:04}_suffix", i).into_bytes())
+            .collect();
+
+        let mut slots = Vec::new();
+        for value in &values {
+            slots.push(p.add_value(value).unwrap());
+        }
+
+        for (slot, value) in slots.iter().zip(&values) {
+            assert_eq!(p.get_value(*slot), Some(value.clone()));
+        }
+    }
+
+    #[test]
+    fn hs_page_prefix_mode_rejects_out_of_order_inserts() {
+        init();
+        let mut p = Page::new_prefix_compressed(0);
+        assert!(p.add_value(b"banana").is_some());
+        assert_eq!(None, p.add_value(b"apple"));
+        // The rejected insert must not have corrupted the page.
+        assert_eq!(p.get_value(0), Some(b"banana".to_vec()));
+    }
+
+    #[test]
+    fn hs_page_prefix_mode_delete_is_unsupported() {
+        init();
+        let mut p = Page::new_prefix_compressed(0);
+        let slot = p.add_value(b"only-value").unwrap();
+        assert_eq!(None, p.delete_value(slot));
+        assert_eq!(p.get_value(slot), Some(b"only-value".to_vec()));
+    }
+
+    #[test]
+    fn hs_page_prefix_mode_into_iter_rematerializes_full_values() {
+        init();
+        let mut p = Page::new_prefix_compressed(0);
+        let values: Vec<Vec<u8>> = (0..20).map(|i| format!("row-{:03}", i).into_bytes()).collect();
+        for value in &values {
+            p.add_value(value).unwrap();
+        }
+
+        let collected: Vec<Vec<u8>> = p.into_iter().map(|(bytes, _)| bytes).collect();
+        assert_eq!(collected, values);
+    }
+
+    #[test]
+    fn hs_page_vacant_slot_reports_its_id_before_fill_and_roundtrips_after() {
+        init();
+        let mut p = Page::new(0);
+        let vacant = p.vacant_slot(11).unwrap();
+        let reserved_id = vacant.slot_id();
+
+        let embedded = format!("id={}", reserved_id);
+        let filled_id = vacant.fill(embedded.as_bytes());
+
+        assert_eq!(reserved_id, filled_id);
+        assert_eq!(p.get_value(filled_id), Some(embedded.into_bytes()));
+    }
+
+    #[test]
+    fn hs_page_vacant_slot_dropped_unfilled_releases_its_reservation() {
+        init();
+        // A filled-then-deleted slot is the existing reclaim behavior this
+        // is meant to match exactly: same free space, same reused id.
+        let mut reference = Page::new(0);
+        let filled_slot = reference.add_value(&[0u8; 64]).unwrap();
+        reference.delete_value(filled_slot).unwrap();
+
+        let mut p = Page::new(0);
+        let vacant = p.vacant_slot(64).unwrap();
+        let reserved_id = vacant.slot_id();
+        drop(vacant);
+
+        assert_eq!(p.get_free_space(), reference.get_free_space());
+
+        // The released id is reused LIFO, exactly as a deleted slot would be.
+        let next_id = p.add_value(b"reused").unwrap();
+        assert_eq!(reserved_id, next_id);
+    }
+
+    #[test]
+    fn hs_page_vacant_slot_fill_can_use_less_than_the_reserved_bound() {
+        init();
+        let mut p = Page::new(0);
+        let vacant = p.vacant_slot(100).unwrap();
+        let slot = vacant.fill(b"short");
+
+        assert_eq!(p.get_value(slot), Some(b"short".to_vec()));
+        // A second value must still fit in the slack left behind.
+        assert!(p.add_value(b"another value").is_some());
+    }
+
+    #[test]
+    fn hs_page_from_bytes_checked_accepts_an_untampered_page() {
+        init();
+        let mut p = Page::new(0);
+        p.add_value(b"some bytes").unwrap();
+
+        let bytes = *p.to_bytes();
+        let restored = Page::from_bytes_checked(bytes).unwrap();
+        assert_eq!(restored.get_value(0), Some(b"some bytes".to_vec()));
+    }
+
+    #[test]
+    fn hs_page_from_bytes_checked_rejects_a_flipped_byte() {
+        init();
+        let mut p = Page::new(0);
+        p.add_value(b"some bytes").unwrap();
+
+        let mut bytes = *p.to_bytes();
+        bytes[PAGE_SIZE - 1] ^= 0xFF;
+
+        match Page::from_bytes_checked(bytes) {
+            Err(CrustyError::CrustyError(msg)) => assert!(msg.contains("CorruptPage")),
+            other => panic!("expected CorruptPage error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn hs_page_par_iter_matches_serial_iterator_on_a_stress_loaded_page() {
+        use rayon::iter::ParallelIterator;
+
+        init();
+        let mut p = Page::new(0);
+        let mut slots = Vec::new();
+        for i in 0..30 {
+            let bytes = get_random_byte_vec(10 + (i % 7) * 13);
+            slots.push(p.add_value(&bytes).unwrap());
+        }
+        // Punch holes like a real stress test would, so `live_slots` has to
+        // skip over some ids rather than just being a dense 0..n range.
+        for &slot in slots.iter().step_by(3) {
+            p.delete_value(slot).unwrap();
+        }
+
+        let mut from_par: Vec<(Vec<u8>, SlotId)> = p.par_iter().unwrap().collect();
+        let mut from_serial: Vec<(Vec<u8>, SlotId)> = p.clone().into_iter().collect();
+        from_par.sort_by_key(|(_, slot)| *slot);
+        from_serial.sort_by_key(|(_, slot)| *slot);
+
+        assert_eq!(from_par, from_serial);
+        assert!(!from_par.is_empty());
+    }
+
+    #[test]
+    fn hs_page_par_iter_rejects_a_prefix_page() {
+        init();
+        let p = Page::new_prefix_compressed(0);
+        assert!(p.par_iter().is_err());
+    }
+
+    #[test]
+    fn hs_page_par_iter_rejects_a_blob_page() {
+        init();
+        let p = Page::new_blob_chunk(0, b"chunk bytes", BLOB_CHAIN_NIL);
+        assert!(p.par_iter().is_err());
+    }
+
+    #[test]
+    fn hs_page_stats_tracks_exact_read_write_counts_for_a_fixed_sequence() {
+        init();
+        let mut p = Page::new(0);
+        assert_eq!(p.stats(), PageStats::default());
+
+        let slot_a = p.add_value(b"abcde").unwrap();
+        let slot_b = p.add_value(b"wxyz").unwrap();
+        assert_eq!(p.get_value(slot_a), Some(b"abcde".to_vec()));
+        assert_eq!(p.get_value(slot_b), Some(b"wxyz".to_vec()));
+        p.delete_value(slot_a).unwrap();
+
+        assert_eq!(
+            p.stats(),
+            PageStats {
+                reads: 2,
+                writes: 3,
+                bytes_read: 9,
+                bytes_written: 14,
+                compactions: 0,
+            }
+        );
+    }
+
+    #[test]
+    fn hs_page_stats_compaction_count_only_rises_when_compaction_is_actually_needed() {
+        init();
+        let mut p = Page::new(0);
+        let size = 800;
+        let values: Vec<Vec<u8>> = (0..5).map(|_| get_random_byte_vec(size)).collect();
+        for v in &values {
+            p.add_value(v).unwrap();
+        }
+        p.delete_value(0).unwrap();
+        assert_eq!(p.stats().compactions, 0);
+
+        // Fits in the leftover contiguous margin -- must not pay for a
+        // compaction pass just to reuse the hole the delete above left.
+        let tiny = get_random_byte_vec(10);
+        p.add_value(&tiny).unwrap();
+        assert_eq!(p.stats().compactions, 0);
+
+        // Only fits once the fragmented hole is reclaimed.
+        let needs_compaction = get_random_byte_vec(size - 20);
+        p.add_value(&needs_compaction).unwrap();
+        assert_eq!(p.stats().compactions, 1);
+    }
+
+    #[test]
+    fn hs_page_add_value_sorted_keeps_slot_headers_in_key_order() {
+        init();
+        let mut p = Page::new(0);
+        p.add_value_sorted(b"d", b"d-val");
+        p.add_value_sorted(b"b", b"b-val");
+        p.add_value_sorted(b"c", b"c-val");
+        p.add_value_sorted(b"a", b"a-val");
+
+        let keys: Vec<Vec<u8>> = (0..p.get_num_slots())
+            .map(|slot| p.sorted_key_at(slot).unwrap())
+            .collect();
+        assert_eq!(
+            keys,
+            vec![b"a".to_vec(), b"b".to_vec(), b"c".to_vec(), b"d".to_vec()]
+        );
+
+        for (slot, key) in keys.iter().enumerate() {
+            assert_eq!(p.search(key), Ok(slot as SlotId));
+        }
+    }
 }
@@ -0,0 +1,774 @@
+use crate::page::Page;
+use common::prelude::*;
+use common::PAGE_SIZE;
+use memmap2::{MmapMut, MmapOptions};
+use std::collections::{HashMap, HashSet};
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU16, Ordering};
+use std::sync::RwLock;
+
+/// Size of the CRC32C checksum trailer written immediately after each page's
+/// `PAGE_SIZE` bytes on disk.
+const CHECKSUM_SIZE: usize = 4;
+/// Total on-disk footprint of one page: its bytes plus the checksum trailer.
+/// Kept separate from `PAGE_SIZE` so the in-memory `Page` layout (and every
+/// test that assumes a page is exactly `PAGE_SIZE` bytes) is untouched.
+const PAGE_STRIDE: usize = PAGE_SIZE + CHECKSUM_SIZE;
+
+/// Magic bytes opening the file-level header of a versioned heap file.
+/// Chosen so it can never be mistaken for the first four bytes of a page
+/// (which are always a little-endian `PageId` the slot 0 page is tagged
+/// with), since a real page 0 never serializes to this value.
+const FORMAT_MAGIC: u32 = 0x4872_7446; // "FtrH" in little-endian bytes
+/// Implicit version of every heap file written before this header existed:
+/// pages packed back-to-back starting at byte 0, no magic, no version.
+const FORMAT_VERSION_UNHEADERED: u16 = 1;
+/// Current on-disk format: [`FORMAT_MAGIC`] + version, followed by pages at
+/// the same per-page layout `FORMAT_VERSION_UNHEADERED` used.
+const CURRENT_FORMAT_VERSION: u16 = 2;
+/// Size of the file-level header: magic(4) + version(2) + reserved(2), kept
+/// a multiple of 4 for alignment of what follows.
+const FORMAT_HEADER_SIZE: usize = 8;
+
+/// Manages the on-disk pages backing a single container.
+///
+/// Rather than issuing a seek+read syscall for every page, the backing file
+/// is memory-mapped so reads are served by slicing directly into the
+/// mapping. The mapping is remapped whenever the file grows (e.g. a new page
+/// is appended), which `num_pages()` reflects by deriving the page count
+/// from the mapped length rather than tracking it separately.
+///
+/// mmap's write-back isn't reliably coherent across clients on a networked
+/// filesystem, so a `HeapFile` opened on a path detected as NFS-backed (see
+/// [`HeapFile::is_nfs_backed`]) falls back to ordinary seek+read/write
+/// syscalls for every page instead of a mapping; `with_page_bytes` then
+/// copies a page into a local buffer rather than slicing the mapping
+/// zero-copy. This is automatic and transparent to callers.
+///
+/// Each page is followed on disk by a 4-byte CRC32C checksum computed over
+/// its `PAGE_SIZE` bytes, recomputed and checked on every read so silent
+/// on-disk corruption surfaces as a `CrustyError` instead of garbage tuples.
+///
+/// The file opens with an 8-byte header (see [`FORMAT_MAGIC`]) identifying
+/// its on-disk format version; pages follow immediately after. Opening a
+/// file written before this header existed (no magic present) transparently
+/// upgrades it in place, so every `HeapFile` in memory only ever has to deal
+/// with [`CURRENT_FORMAT_VERSION`]. See [`HeapFile::upgrade_to_current_format`].
+///
+/// Also tracks which transactions have committed, so MVCC-aware readers can
+/// build a [`crate::heap_page::Snapshot`] (via [`HeapFile::with_snapshot`])
+/// that decides whether a tuple version's `xmin`/`xmax` stamp is visible to
+/// them.
+pub struct HeapFile {
+    pub container_id: ContainerId,
+    file: RwLock<File>,
+    /// `None` until the file holds at least one page; mmap requires a
+    /// non-empty backing file, so an empty container has no mapping yet.
+    /// Always `None` when `use_mmap` is false.
+    mmap: RwLock<Option<MmapMut>>,
+    /// False when this file's directory was detected as NFS-backed; see the
+    /// struct-level docs and [`HeapFile::is_nfs_backed`]. Reads and writes
+    /// check this on every call rather than mmap being torn down, since an
+    /// NFS-backed file never has one to tear down in the first place.
+    use_mmap: bool,
+    pub read_count: AtomicU16,
+    pub write_count: AtomicU16,
+    /// When false, skip checksum verification on read (hot-path escape
+    /// hatch for callers that already trust the data, e.g. a restore from a
+    /// just-verified snapshot).
+    verify_checksums: bool,
+    /// Transactions known to have committed, consulted by MVCC-aware reads
+    /// to decide whether a version's `xmin`/`xmax` stamp is visible to a
+    /// given snapshot. A transaction that never appears here is treated as
+    /// still in-flight (or aborted), so its writes stay invisible to anyone
+    /// but itself.
+    committed: RwLock<HashSet<TransactionId>>,
+    /// Per-transaction log of writes made through the `_mvcc` family of
+    /// methods, consulted by [`HeapFile::rollback_to_savepoint`] to undo a
+    /// subrange of a transaction's own inserts/deletes.
+    mvcc_log: RwLock<HashMap<TransactionId, Vec<MvccWrite>>>,
+}
+
+/// One write a transaction made through the `_mvcc` API, recorded so a
+/// savepoint can later undo it by resetting the stamp it applied.
+#[derive(Clone, Copy)]
+enum MvccWrite {
+    /// Created a new version at `slot_id` on `page_id`; undone by removing
+    /// it outright.
+    Insert { page_id: PageId, slot_id: SlotId },
+    /// Stamped `slot_id` on `page_id` as deleted; undone by clearing the
+    /// stamp.
+    Delete { page_id: PageId, slot_id: SlotId },
+}
+
+/// A marker returned by [`HeapFile::savepoint`], identifying a point in a
+/// transaction's undo log that [`HeapFile::rollback_to_savepoint`] can later
+/// unwind back to, mirroring the set/rollback-to/release savepoint API of an
+/// optimistic transaction store.
+pub struct Savepoint(usize);
+
+impl HeapFile {
+    /// Open (creating if necessary) the heap file backing `path`, mapping it
+    /// into memory if it already holds data. Checksum verification is on by
+    /// default; use [`HeapFile::new_with_config`] to disable it.
+    pub fn new(path: PathBuf, container_id: ContainerId) -> Result<Self, CrustyError> {
+        Self::new_with_config(path, container_id, true)
+    }
+
+    /// Like [`HeapFile::new`], but lets the caller toggle checksum
+    /// verification on read.
+    pub fn new_with_config(
+        path: PathBuf,
+        container_id: ContainerId,
+        verify_checksums: bool,
+    ) -> Result<Self, CrustyError> {
+        Self::upgrade_to_current_format(&path)?;
+
+        let use_mmap = !Self::is_nfs_backed(path.parent().unwrap_or_else(|| Path::new(".")));
+
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .open(&path)
+            .map_err(|e| CrustyError::CrustyError(format!("Could not open heapfile: {}", e)))?;
+
+        let len = file
+            .metadata()
+            .map_err(|e| CrustyError::CrustyError(format!("Could not stat heapfile: {}", e)))?
+            .len();
+
+        // A file holding nothing but its format header (no pages yet) maps
+        // to `None` just like a freshly created one did before headers
+        // existed, so `num_pages`/`with_page_bytes` don't need to special-case
+        // "header-only" from "doesn't exist".
+        let mmap =
+            if !use_mmap || len <= FORMAT_HEADER_SIZE as u64 {
+                None
+            } else {
+                Some(unsafe { MmapOptions::new().map_mut(&file) }.map_err(|e| {
+                    CrustyError::CrustyError(format!("Could not mmap heapfile: {}", e))
+                })?)
+            };
+
+        Ok(Self {
+            container_id,
+            file: RwLock::new(file),
+            mmap: RwLock::new(mmap),
+            use_mmap,
+            read_count: AtomicU16::new(0),
+            write_count: AtomicU16::new(0),
+            verify_checksums,
+            committed: RwLock::new(HashSet::new()),
+            mvcc_log: RwLock::new(HashMap::new()),
+        })
+    }
+
+    /// Parse mountinfo-format text (see proc(5)) and decide whether the
+    /// mount point that most specifically contains `path` reports an NFS
+    /// filesystem type. Split out from [`HeapFile::is_nfs_backed`] so the
+    /// matching logic can be unit tested without a real NFS mount.
+    fn path_is_nfs(mountinfo: &str, path: &Path) -> bool {
+        let path_str = path.to_string_lossy();
+        let mut best_match: Option<(usize, bool)> = None;
+        for line in mountinfo.lines() {
+            // Mount point is the 5th whitespace-separated field; filesystem
+            // type is the first field after the literal " - " separator.
+            let (pre, post) = match line.split_once(" - ") {
+                Some(parts) => parts,
+                None => continue,
+            };
+            let mount_point = match pre.split_whitespace().nth(4) {
+                Some(mp) => mp,
+                None => continue,
+            };
+            let fs_type = match post.split_whitespace().next() {
+                Some(t) => t,
+                None => continue,
+            };
+            if path_str.starts_with(mount_point)
+                && mount_point.len() > best_match.map_or(0, |(len, _)| len)
+            {
+                best_match = Some((mount_point.len(), fs_type.starts_with("nfs")));
+            }
+        }
+        best_match.map_or(false, |(_, is_nfs)| is_nfs)
+    }
+
+    /// Best-effort detection of whether `dir` lives on an NFS mount, so
+    /// [`HeapFile::new_with_config`] can fall back away from mmap for it.
+    /// Any failure to read or parse `/proc/self/mountinfo` (non-Linux, no
+    /// `/proc`, a sandboxed container) is treated as "not NFS" so mmap stays
+    /// the default.
+    fn is_nfs_backed(dir: &Path) -> bool {
+        let canonical = dir.canonicalize().unwrap_or_else(|_| dir.to_path_buf());
+        match std::fs::read_to_string("/proc/self/mountinfo") {
+            Ok(mountinfo) => Self::path_is_nfs(&mountinfo, &canonical),
+            Err(_) => false,
+        }
+    }
+
+    /// Record that `tid` has committed, making its writes visible to any
+    /// snapshot taken afterwards.
+    pub fn mark_committed(&self, tid: TransactionId) {
+        self.committed.write().unwrap().insert(tid);
+    }
+
+    /// Whether `tid` has been recorded as committed via [`mark_committed`].
+    pub fn is_committed(&self, tid: TransactionId) -> bool {
+        self.committed.read().unwrap().contains(&tid)
+    }
+
+    /// Run `f` with a [`Snapshot`] for a scan running as `tid`: its own
+    /// writes are visible, plus anything already committed by the time the
+    /// snapshot is taken. Mirrors the closure-passing shape of
+    /// [`HeapFile::with_page_bytes`] so the borrowed committed set never has
+    /// to escape this call.
+    pub(crate) fn with_snapshot<T>(
+        &self,
+        tid: TransactionId,
+        f: impl FnOnce(&crate::heap_page::Snapshot) -> T,
+    ) -> T {
+        let committed = self.committed.read().unwrap();
+        let snapshot = crate::heap_page::Snapshot::new(tid, &committed);
+        f(&snapshot)
+    }
+
+    /// Insert `bytes` as a new MVCC-versioned tuple created by `tid` on
+    /// `page_id`, recording the write in `tid`'s undo log so a savepoint can
+    /// later roll it back.
+    pub fn insert_value_mvcc(
+        &self,
+        page_id: PageId,
+        bytes: &[u8],
+        tid: TransactionId,
+    ) -> Result<SlotId, CrustyError> {
+        let mut page = self.read_page_from_file(page_id)?;
+        let slot_id = page
+            .add_value_mvcc(bytes, tid)
+            .ok_or_else(|| CrustyError::CrustyError("Page out of space".to_string()))?;
+        self.write_page_to_file(&page)?;
+        self.mvcc_log
+            .write()
+            .unwrap()
+            .entry(tid)
+            .or_default()
+            .push(MvccWrite::Insert { page_id, slot_id });
+        Ok(slot_id)
+    }
+
+    /// Stamp `slot_id` on `page_id` as deleted by `tid`, recording the write
+    /// in `tid`'s undo log. Returns the slot id the stamped record now lives
+    /// at (see [`crate::heap_page::Page::delete_value_mvcc`]).
+    pub fn delete_value_mvcc(
+        &self,
+        page_id: PageId,
+        slot_id: SlotId,
+        tid: TransactionId,
+    ) -> Result<SlotId, CrustyError> {
+        let mut page = self.read_page_from_file(page_id)?;
+        let new_slot_id = page
+            .delete_value_mvcc(slot_id, tid)
+            .ok_or_else(|| CrustyError::CrustyError("Slot not found".to_string()))?;
+        self.write_page_to_file(&page)?;
+        self.mvcc_log
+            .write()
+            .unwrap()
+            .entry(tid)
+            .or_default()
+            .push(MvccWrite::Delete {
+                page_id,
+                slot_id: new_slot_id,
+            });
+        Ok(new_slot_id)
+    }
+
+    /// Record a savepoint for `tid` at its current undo-log position.
+    pub fn savepoint(&self, tid: TransactionId) -> Savepoint {
+        let log = self.mvcc_log.read().unwrap();
+        Savepoint(log.get(&tid).map_or(0, |writes| writes.len()))
+    }
+
+    /// Undo every write `tid` has made since `savepoint`, resetting the
+    /// `xmin`/`xmax` stamps those writes applied, then drop them from the
+    /// undo log so this savepoint can't be rolled back to a second time.
+    pub fn rollback_to_savepoint(
+        &self,
+        tid: TransactionId,
+        savepoint: Savepoint,
+    ) -> Result<(), CrustyError> {
+        let mut log = self.mvcc_log.write().unwrap();
+        let writes = log.entry(tid).or_default();
+        while writes.len() > savepoint.0 {
+            match writes.pop().unwrap() {
+                MvccWrite::Insert { page_id, slot_id } => {
+                    let mut page = self.read_page_from_file(page_id)?;
+                    page.undo_insert_mvcc(slot_id);
+                    self.write_page_to_file(&page)?;
+                }
+                MvccWrite::Delete { page_id, slot_id } => {
+                    let mut page = self.read_page_from_file(page_id)?;
+                    page.undo_delete_mvcc(slot_id);
+                    self.write_page_to_file(&page)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Release `savepoint` without rolling back: `tid`'s writes since then
+    /// stay applied. Consuming `savepoint` is what keeps it from being rolled
+    /// back to afterwards; the log entries stay in place, so a rollback to an
+    /// earlier savepoint still undoes them. Otherwise a no-op, but kept as an
+    /// explicit call so callers don't have to special-case "done with this
+    /// savepoint" versus "never took one".
+    pub fn release_savepoint(&self, _tid: TransactionId, _savepoint: Savepoint) {}
+
+    /// The number of pages currently backing this file, derived from the
+    /// length of the memory mapping (or, for an NFS-backed file with no
+    /// mapping, the file's own length) rather than a separately tracked
+    /// count.
+    pub fn num_pages(&self) -> PageId {
+        let len = match &*self.mmap.read().unwrap() {
+            Some(mmap) => mmap.len(),
+            None if self.use_mmap => 0,
+            None => self
+                .file
+                .read()
+                .unwrap()
+                .metadata()
+                .map(|m| m.len() as usize)
+                .unwrap_or(0),
+        };
+        if len <= FORMAT_HEADER_SIZE {
+            0
+        } else {
+            ((len - FORMAT_HEADER_SIZE) / PAGE_STRIDE) as PageId
+        }
+    }
+
+    /// Ensure `path` is on [`CURRENT_FORMAT_VERSION`] before it's opened,
+    /// rewriting it in place if necessary. A missing or empty file needs
+    /// only a fresh header written ahead of it; an existing file with no
+    /// [`FORMAT_MAGIC`] predates the header entirely ([`FORMAT_VERSION_UNHEADERED`])
+    /// and is upgraded by prepending one to its existing page bytes, which
+    /// need no reshaping since the per-page layout hasn't changed. The
+    /// rewrite goes through a temporary file and an atomic rename so a crash
+    /// mid-upgrade can't leave a half-written file behind.
+    fn upgrade_to_current_format(path: &PathBuf) -> Result<(), CrustyError> {
+        let existing = match std::fs::read(path) {
+            Ok(bytes) => bytes,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+            Err(e) => {
+                return Err(CrustyError::CrustyError(format!(
+                    "Could not read heapfile for format check: {}",
+                    e
+                )))
+            }
+        };
+
+        if existing.is_empty() {
+            let mut header = Vec::with_capacity(FORMAT_HEADER_SIZE);
+            header.extend_from_slice(&FORMAT_MAGIC.to_le_bytes());
+            header.extend_from_slice(&CURRENT_FORMAT_VERSION.to_le_bytes());
+            header.extend_from_slice(&[0u8; 2]);
+            return std::fs::write(path, header).map_err(|e| {
+                CrustyError::CrustyError(format!("Could not write heapfile header: {}", e))
+            });
+        }
+
+        let detected_version = if existing.len() >= FORMAT_HEADER_SIZE
+            && existing[0..4] == FORMAT_MAGIC.to_le_bytes()
+        {
+            u16::from_le_bytes(existing[4..6].try_into().unwrap())
+        } else {
+            FORMAT_VERSION_UNHEADERED
+        };
+        if detected_version == CURRENT_FORMAT_VERSION {
+            return Ok(());
+        }
+
+        // Only FORMAT_VERSION_UNHEADERED has ever existed below
+        // CURRENT_FORMAT_VERSION, so that's the only upgrade this knows how
+        // to perform; prepend the current header to the existing page bytes,
+        // which need no reshaping since the per-page layout hasn't changed.
+        let tmp_path = path.with_extension("upgrading");
+        let mut upgraded = Vec::with_capacity(FORMAT_HEADER_SIZE + existing.len());
+        upgraded.extend_from_slice(&FORMAT_MAGIC.to_le_bytes());
+        upgraded.extend_from_slice(&CURRENT_FORMAT_VERSION.to_le_bytes());
+        upgraded.extend_from_slice(&[0u8; 2]);
+        upgraded.extend_from_slice(&existing);
+
+        std::fs::write(&tmp_path, &upgraded).map_err(|e| {
+            CrustyError::CrustyError(format!("Could not write upgraded heapfile: {}", e))
+        })?;
+        std::fs::rename(&tmp_path, path).map_err(|e| {
+            CrustyError::CrustyError(format!("Could not install upgraded heapfile: {}", e))
+        })?;
+        Ok(())
+    }
+
+    /// Run `f` with zero-copy, read-only access to the raw `PAGE_SIZE` bytes
+    /// for `page_id` as they sit in the memory mapping (no syscall, no page
+    /// allocation, no clone of the page). Only the value `f` returns escapes
+    /// this call, so callers should copy out only what they actually need.
+    /// Returns a `CrustyError::CrustyError` whose message starts with
+    /// `CorruptPage` if the stored checksum no longer matches the page bytes
+    /// and verification is enabled.
+    pub(crate) fn with_page_bytes<T>(
+        &self,
+        page_id: PageId,
+        f: impl FnOnce(&[u8]) -> T,
+    ) -> Result<T, CrustyError> {
+        if !self.use_mmap {
+            return self.with_page_bytes_direct(page_id, f);
+        }
+        let guard = self.mmap.read().unwrap();
+        match &*guard {
+            Some(mmap)
+                if FORMAT_HEADER_SIZE + (page_id as usize + 1) * PAGE_STRIDE <= mmap.len() =>
+            {
+                let start = FORMAT_HEADER_SIZE + page_id as usize * PAGE_STRIDE;
+                let page_bytes = &mmap[start..start + PAGE_SIZE];
+                if self.verify_checksums {
+                    let stored = u32::from_le_bytes(
+                        mmap[start + PAGE_SIZE..start + PAGE_STRIDE]
+                            .try_into()
+                            .unwrap(),
+                    );
+                    if crc32c::crc32c(page_bytes) != stored {
+                        return Err(CrustyError::CrustyError(format!(
+                            "CorruptPage: checksum mismatch for page {}",
+                            page_id
+                        )));
+                    }
+                }
+                Ok(f(page_bytes))
+            }
+            _ => Err(CrustyError::CrustyError(format!(
+                "Page {} does not exist",
+                page_id
+            ))),
+        }
+    }
+
+    /// `with_page_bytes` fallback for an NFS-backed file: read the page's
+    /// `PAGE_STRIDE` bytes into a local buffer via seek+read rather than
+    /// slicing a mapping, then hand `f` a view into that buffer. Not
+    /// zero-copy, but avoids relying on mmap's write-back being coherent
+    /// over NFS.
+    fn with_page_bytes_direct<T>(
+        &self,
+        page_id: PageId,
+        f: impl FnOnce(&[u8]) -> T,
+    ) -> Result<T, CrustyError> {
+        let start = FORMAT_HEADER_SIZE + page_id as usize * PAGE_STRIDE;
+        let mut buf = [0u8; PAGE_STRIDE];
+        {
+            let mut file = self.file.write().unwrap();
+            file.seek(SeekFrom::Start(start as u64)).map_err(|e| {
+                CrustyError::CrustyError(format!("Page {} does not exist: {}", page_id, e))
+            })?;
+            file.read_exact(&mut buf).map_err(|e| {
+                CrustyError::CrustyError(format!("Page {} does not exist: {}", page_id, e))
+            })?;
+        }
+        let page_bytes = &buf[0..PAGE_SIZE];
+        if self.verify_checksums {
+            let stored = u32::from_le_bytes(buf[PAGE_SIZE..PAGE_STRIDE].try_into().unwrap());
+            if crc32c::crc32c(page_bytes) != stored {
+                return Err(CrustyError::CrustyError(format!(
+                    "CorruptPage: checksum mismatch for page {}",
+                    page_id
+                )));
+            }
+        }
+        Ok(f(page_bytes))
+    }
+
+    /// Read the page for `page_id` out of the mapping into an owned `Page`.
+    /// When `verify_checksums` is set, also verifies the page's own embedded
+    /// CRC32C (see `Page::from_bytes_checked`) on top of the file-level
+    /// trailer `with_page_bytes` already checked -- same toggle, since both
+    /// exist to catch the same kind of on-disk corruption.
+    pub fn read_page_from_file(&self, page_id: PageId) -> Result<Page, CrustyError> {
+        let bytes: [u8; PAGE_SIZE] = self.with_page_bytes(page_id, |b| b.try_into().unwrap())?;
+        self.read_count.fetch_add(1, Ordering::Relaxed);
+        if self.verify_checksums {
+            Page::from_bytes_checked(bytes)
+        } else {
+            Ok(Page::from_bytes(bytes))
+        }
+    }
+
+    /// Write `page` to its slot in the file (plus a freshly computed
+    /// checksum trailer), growing and remapping the file first if the page
+    /// lies beyond the current mapping.
+    pub fn write_page_to_file(&self, page: &Page) -> Result<(), CrustyError> {
+        let page_id = page.get_page_id();
+        let needed_len = FORMAT_HEADER_SIZE + (page_id as usize + 1) * PAGE_STRIDE;
+
+        if !self.use_mmap {
+            self.write_page_direct(page_id, page, needed_len)?;
+            self.write_count.fetch_add(1, Ordering::Relaxed);
+            return Ok(());
+        }
+
+        let cur_len = match &*self.mmap.read().unwrap() {
+            Some(mmap) => mmap.len(),
+            None => FORMAT_HEADER_SIZE,
+        };
+        if cur_len < needed_len {
+            self.grow_to(needed_len)?;
+        }
+
+        let mut guard = self.mmap.write().unwrap();
+        let mmap = guard.as_mut().expect("mapping must exist after grow_to");
+        let start = FORMAT_HEADER_SIZE + page_id as usize * PAGE_STRIDE;
+        let bytes = page.to_bytes();
+        mmap[start..start + PAGE_SIZE].copy_from_slice(bytes.as_ref());
+        let checksum = crc32c::crc32c(bytes.as_ref());
+        mmap[start + PAGE_SIZE..start + PAGE_STRIDE].copy_from_slice(&checksum.to_le_bytes());
+        self.write_count.fetch_add(1, Ordering::Relaxed);
+        Ok(())
+    }
+
+    /// `write_page_to_file` fallback for an NFS-backed file: grow the file
+    /// if needed, then seek+write the page bytes and checksum trailer
+    /// directly rather than through a mapping.
+    fn write_page_direct(
+        &self,
+        page_id: PageId,
+        page: &Page,
+        needed_len: usize,
+    ) -> Result<(), CrustyError> {
+        let mut file = self.file.write().unwrap();
+        let cur_len = file
+            .metadata()
+            .map_err(|e| CrustyError::CrustyError(format!("Could not stat heapfile: {}", e)))?
+            .len();
+        if cur_len < needed_len as u64 {
+            file.set_len(needed_len as u64)
+                .map_err(|e| CrustyError::CrustyError(format!("Could not grow heapfile: {}", e)))?;
+        }
+
+        let start = FORMAT_HEADER_SIZE + page_id as usize * PAGE_STRIDE;
+        let bytes = page.to_bytes();
+        let checksum = crc32c::crc32c(bytes.as_ref());
+        file.seek(SeekFrom::Start(start as u64))
+            .map_err(|e| CrustyError::CrustyError(format!("Could not seek heapfile: {}", e)))?;
+        file.write_all(bytes.as_ref())
+            .map_err(|e| CrustyError::CrustyError(format!("Could not write heapfile: {}", e)))?;
+        file.write_all(&checksum.to_le_bytes())
+            .map_err(|e| CrustyError::CrustyError(format!("Could not write heapfile: {}", e)))?;
+        Ok(())
+    }
+
+    /// Scan every page in this file and return the set of `PageId`s whose
+    /// stored checksum no longer matches their bytes.
+    pub fn verify(&self) -> Vec<PageId> {
+        let mut corrupt = Vec::new();
+        for page_id in 0..self.num_pages() {
+            if self.with_page_bytes(page_id, |_| ()).is_err() {
+                corrupt.push(page_id);
+            }
+        }
+        corrupt
+    }
+
+    /// Extend the backing file to `len` bytes and remap it.
+    fn grow_to(&self, len: usize) -> Result<(), CrustyError> {
+        let file = self.file.write().unwrap();
+        file.set_len(len as u64)
+            .map_err(|e| CrustyError::CrustyError(format!("Could not grow heapfile: {}", e)))?;
+        let new_mmap = unsafe { MmapOptions::new().map_mut(&*file) }
+            .map_err(|e| CrustyError::CrustyError(format!("Could not remap heapfile: {}", e)))?;
+        *self.mmap.write().unwrap() = Some(new_mmap);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::heap_page::HeapPage;
+    use common::testutil::gen_random_test_sm_dir;
+
+    fn new_test_heapfile() -> (HeapFile, PathBuf) {
+        let dir = gen_random_test_sm_dir();
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("test_container");
+        (HeapFile::new(path.clone(), 1).unwrap(), path)
+    }
+
+    #[test]
+    fn hf_checksum_detects_corruption() {
+        let (hf, path) = new_test_heapfile();
+        let page = Page::new(0);
+        hf.write_page_to_file(&page).unwrap();
+        assert!(hf.read_page_from_file(0).is_ok());
+        drop(hf);
+
+        // Flip a byte in the persisted page payload (not the checksum
+        // trailer) to simulate on-disk corruption.
+        let mut bytes = std::fs::read(&path).unwrap();
+        bytes[10] ^= 0xFF;
+        std::fs::write(&path, &bytes).unwrap();
+
+        let hf = HeapFile::new(path.clone(), 1).unwrap();
+        match hf.read_page_from_file(0) {
+            Err(CrustyError::CrustyError(msg)) => assert!(msg.contains("CorruptPage")),
+            other => panic!("expected CorruptPage error, got {:?}", other),
+        }
+        std::fs::remove_dir_all(path.parent().unwrap()).ok();
+    }
+
+    #[test]
+    fn hf_verify_reports_corrupt_pages() {
+        let (hf, path) = new_test_heapfile();
+        hf.write_page_to_file(&Page::new(0)).unwrap();
+        hf.write_page_to_file(&Page::new(1)).unwrap();
+        drop(hf);
+
+        let mut bytes = std::fs::read(&path).unwrap();
+        bytes[PAGE_STRIDE + 20] ^= 0xFF;
+        std::fs::write(&path, &bytes).unwrap();
+
+        let hf = HeapFile::new(path.clone(), 1).unwrap();
+        assert_eq!(hf.verify(), vec![1]);
+        std::fs::remove_dir_all(path.parent().unwrap()).ok();
+    }
+
+    #[test]
+    fn hf_checksum_can_be_disabled() {
+        let dir = gen_random_test_sm_dir();
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("test_container");
+        let hf = HeapFile::new_with_config(path.clone(), 1, false).unwrap();
+        hf.write_page_to_file(&Page::new(0)).unwrap();
+        drop(hf);
+
+        let mut bytes = std::fs::read(&path).unwrap();
+        bytes[10] ^= 0xFF;
+        std::fs::write(&path, &bytes).unwrap();
+
+        let hf = HeapFile::new_with_config(path.clone(), 1, false).unwrap();
+        assert!(hf.read_page_from_file(0).is_ok());
+        std::fs::remove_dir_all(path.parent().unwrap()).ok();
+    }
+
+    #[test]
+    fn hf_mvcc_uncommitted_write_invisible_to_concurrent_reader() {
+        let (hf, path) = new_test_heapfile();
+        hf.write_page_to_file(&Page::new(0)).unwrap();
+
+        let writer = TransactionId::new();
+        let reader = TransactionId::new();
+        hf.insert_value_mvcc(0, b"hello", writer).unwrap();
+
+        hf.with_snapshot(reader, |snapshot| {
+            let page = hf.read_page_from_file(0).unwrap();
+            assert_eq!(page.get_value_mvcc(0, snapshot), None);
+        });
+
+        hf.mark_committed(writer);
+        hf.with_snapshot(reader, |snapshot| {
+            let page = hf.read_page_from_file(0).unwrap();
+            assert_eq!(page.get_value_mvcc(0, snapshot), Some(b"hello".to_vec()));
+        });
+
+        std::fs::remove_dir_all(path.parent().unwrap()).ok();
+    }
+
+    #[test]
+    fn hf_new_file_gets_current_format_header() {
+        let dir = gen_random_test_sm_dir();
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("test_container");
+        let hf = HeapFile::new(path.clone(), 1).unwrap();
+        hf.write_page_to_file(&Page::new(0)).unwrap();
+        drop(hf);
+
+        let bytes = std::fs::read(&path).unwrap();
+        assert_eq!(bytes[0..4], FORMAT_MAGIC.to_le_bytes());
+        assert_eq!(
+            u16::from_le_bytes(bytes[4..6].try_into().unwrap()),
+            CURRENT_FORMAT_VERSION
+        );
+        std::fs::remove_dir_all(path.parent().unwrap()).ok();
+    }
+
+    #[test]
+    fn hf_upgrades_unheadered_legacy_file_in_place() {
+        let dir = gen_random_test_sm_dir();
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("test_container");
+
+        // Hand-write a legacy (pre-header) file: a single page plus its
+        // checksum trailer, starting at byte 0.
+        let page = Page::new(0);
+        let page_bytes = page.to_bytes();
+        let mut legacy = Vec::new();
+        legacy.extend_from_slice(page_bytes.as_ref());
+        legacy.extend_from_slice(&crc32c::crc32c(page_bytes.as_ref()).to_le_bytes());
+        std::fs::write(&path, &legacy).unwrap();
+
+        let hf = HeapFile::new(path.clone(), 1).unwrap();
+        assert_eq!(hf.num_pages(), 1);
+        assert_eq!(hf.read_page_from_file(0).unwrap().get_page_id(), 0);
+
+        let upgraded = std::fs::read(&path).unwrap();
+        assert_eq!(upgraded[0..4], FORMAT_MAGIC.to_le_bytes());
+        assert_eq!(upgraded.len(), FORMAT_HEADER_SIZE + legacy.len());
+
+        std::fs::remove_dir_all(path.parent().unwrap()).ok();
+    }
+
+    #[test]
+    fn hf_path_is_nfs_matches_most_specific_mount() {
+        let mountinfo = "\
+25 30 0:24 / / rw,relatime shared:1 - ext4 /dev/sda1 rw\n\
+36 25 98:0 / /mnt/nfsshare rw,relatime shared:2 - nfs4 server:/export rw\n";
+
+        assert!(!HeapFile::path_is_nfs(
+            mountinfo,
+            Path::new("/var/lib/data")
+        ));
+        assert!(HeapFile::path_is_nfs(
+            mountinfo,
+            Path::new("/mnt/nfsshare/heapstore")
+        ));
+    }
+
+    #[test]
+    fn hf_path_is_nfs_defaults_false_on_no_match() {
+        assert!(!HeapFile::path_is_nfs("", Path::new("/var/lib/data")));
+    }
+
+    #[test]
+    fn hf_mvcc_rollback_to_savepoint_undoes_later_writes() {
+        let (hf, path) = new_test_heapfile();
+        hf.write_page_to_file(&Page::new(0)).unwrap();
+
+        let tid = TransactionId::new();
+        let first_slot = hf.insert_value_mvcc(0, b"kept", tid).unwrap();
+        let savepoint = hf.savepoint(tid);
+        hf.insert_value_mvcc(0, b"undone", tid).unwrap();
+
+        hf.rollback_to_savepoint(tid, savepoint).unwrap();
+
+        hf.with_snapshot(tid, |snapshot| {
+            let page = hf.read_page_from_file(0).unwrap();
+            assert_eq!(
+                page.get_value_mvcc(first_slot, snapshot),
+                Some(b"kept".to_vec())
+            );
+        });
+        let undone_page = hf.read_page_from_file(0).unwrap();
+        assert_eq!(undone_page.get_value(first_slot + 1), None);
+
+        std::fs::remove_dir_all(path.parent().unwrap()).ok();
+    }
+}
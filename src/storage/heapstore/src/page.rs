@@ -0,0 +1,147 @@
+use common::ids::{PageId, SlotId};
+use common::prelude::*;
+use common::PAGE_SIZE;
+use std::fmt;
+
+/// Type used to express byte offsets within a page.
+pub type Offset = u16;
+
+/// A fixed-size page of bytes, identified by a `PageId`, used as the unit of
+/// storage for a `HeapFile`. All layout (slot directory, free space, etc.) is
+/// interpreted by the `HeapPage` trait implemented for this type.
+#[derive(Clone)]
+pub struct Page {
+    pub(crate) data: Box<[u8; PAGE_SIZE]>,
+    /// Runtime-only read/write/compaction counters; see `PageStats`. Never
+    /// serialized -- a page read back via `from_bytes` starts fresh.
+    pub(crate) counters: crate::heap_page::PageCounters,
+}
+
+impl Page {
+    /// Create a new, empty page tagged with `page_id`.
+    pub fn new(page_id: PageId) -> Self {
+        let mut data = Box::new([0u8; PAGE_SIZE]);
+        data[0..std::mem::size_of::<PageId>()].copy_from_slice(&page_id.to_le_bytes());
+        // A fresh page has no free slots yet, so its intrusive free list
+        // (see heap_page::FREELIST_HEAD_LOC) starts out empty.
+        let loc = crate::heap_page::FREELIST_HEAD_LOC;
+        data[loc..loc + std::mem::size_of::<SlotId>()]
+            .copy_from_slice(&crate::heap_page::FREELIST_NIL.to_le_bytes());
+        Self {
+            data,
+            counters: Default::default(),
+        }
+    }
+
+    /// Returns the id stored in this page's header.
+    pub fn get_page_id(&self) -> PageId {
+        PageId::from_le_bytes(
+            self.data[0..std::mem::size_of::<PageId>()]
+                .try_into()
+                .unwrap(),
+        )
+    }
+
+    /// Serialize the page to its fixed-size, on-disk byte representation.
+    /// If a non-identity codec is configured (see `set_compressor`), the
+    /// packed-record region below `first_offset` is compressed in place and
+    /// the spare room left behind is zeroed; `from_bytes` inflates it back
+    /// before rebuilding the slot map. The codec id actually used -- which
+    /// falls back to identity if compressing wouldn't have shrunk anything
+    /// -- is written into this snapshot's header byte, independent of
+    /// whatever `self` is still configured with.
+    ///
+    /// The very last step stamps a CRC32C over everything else into
+    /// `CHECKSUM_LOC`, so `from_bytes_checked` can tell a torn or bit-rotted
+    /// copy of these bytes from a good one.
+    pub fn to_bytes(&self) -> Box<[u8; PAGE_SIZE]> {
+        let mut out = self.data.clone();
+        let id = self.get_compressor_id();
+        if id != crate::heap_page::IDENTITY_COMPRESSOR_ID {
+            if let Some(codec) = crate::heap_page::compressor_for_id(id) {
+                let first_offset = crate::heap_page::raw_first_offset(&self.data) as usize;
+                let region = &self.data[first_offset..PAGE_SIZE];
+                let compressed = codec.compress(region);
+                if compressed.len() < region.len() {
+                    let end = first_offset + compressed.len();
+                    out[first_offset..end].copy_from_slice(&compressed);
+                    out[end..PAGE_SIZE].fill(0);
+                } else {
+                    out[crate::heap_page::COMPRESSOR_ID_LOC] =
+                        crate::heap_page::IDENTITY_COMPRESSOR_ID;
+                }
+            }
+        }
+
+        let loc = crate::heap_page::CHECKSUM_LOC;
+        out[loc..loc + crate::heap_page::CHECKSUM_SIZE].fill(0);
+        let checksum = crc32c::crc32c(out.as_ref());
+        out[loc..loc + crate::heap_page::CHECKSUM_SIZE].copy_from_slice(&checksum.to_le_bytes());
+        out
+    }
+
+    /// Reconstruct a page from its on-disk byte representation, inflating
+    /// the packed-record region first if its header names a non-identity
+    /// codec (see `to_bytes`), then repopulating the embedded Bloom filter
+    /// (see `may_contain`) from the slots that come back out of that --
+    /// it's already correct in `data` as stored, but re-deriving it here
+    /// keeps `from_bytes` the single place that has to be trusted for it.
+    pub fn from_bytes(data: [u8; PAGE_SIZE]) -> Self {
+        let id = data[crate::heap_page::COMPRESSOR_ID_LOC];
+        let mut page = match crate::heap_page::compressor_for_id(id) {
+            Some(codec) if id != crate::heap_page::IDENTITY_COMPRESSOR_ID => {
+                let first_offset = crate::heap_page::raw_first_offset(&data) as usize;
+                let inflated = codec.decompress(&data[first_offset..PAGE_SIZE]);
+                let mut restored = data;
+                restored[first_offset..first_offset + inflated.len()].copy_from_slice(&inflated);
+                Self {
+                    data: Box::new(restored),
+                    counters: Default::default(),
+                }
+            }
+            _ => Self {
+                data: Box::new(data),
+                counters: Default::default(),
+            },
+        };
+        if page.get_page_type() == crate::heap_page::PageType::Heap {
+            page.rebuild_filter();
+        }
+        page
+    }
+
+    /// Like `from_bytes`, but recomputes the CRC32C `to_bytes` stamped into
+    /// `CHECKSUM_LOC` first and refuses to build a page if it doesn't match
+    /// -- the check `from_bytes` skips, trusting `data` completely, which is
+    /// fine for bytes this process just produced but not for ones read back
+    /// off disk that may have been torn or bit-rotted in between.
+    pub fn from_bytes_checked(data: [u8; PAGE_SIZE]) -> Result<Self, CrustyError> {
+        let loc = crate::heap_page::CHECKSUM_LOC;
+        let stored = u32::from_le_bytes(
+            data[loc..loc + crate::heap_page::CHECKSUM_SIZE]
+                .try_into()
+                .unwrap(),
+        );
+        let mut unchecksummed = data;
+        unchecksummed[loc..loc + crate::heap_page::CHECKSUM_SIZE].fill(0);
+        let actual = crc32c::crc32c(&unchecksummed);
+        if actual != stored {
+            let page_id = PageId::from_le_bytes(
+                data[0..std::mem::size_of::<PageId>()].try_into().unwrap(),
+            );
+            return Err(CrustyError::CrustyError(format!(
+                "CorruptPage: checksum mismatch for page {}",
+                page_id
+            )));
+        }
+        Ok(Self::from_bytes(data))
+    }
+}
+
+impl fmt::Debug for Page {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Page")
+            .field("page_id", &self.get_page_id())
+            .finish()
+    }
+}